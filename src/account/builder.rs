@@ -6,7 +6,11 @@ use crate::account::constants::DEFAULT_LEDGER_OUTPUT_CONSOLIDATION_THRESHOLD;
 #[cfg(feature = "events")]
 use crate::events::EventEmitter;
 use crate::{
-    account::{constants::DEFAULT_OUTPUT_CONSOLIDATION_THRESHOLD, handle::AccountHandle, Account, AccountOptions},
+    account::{
+        constants::DEFAULT_OUTPUT_CONSOLIDATION_THRESHOLD, handle::AccountHandle, Account, AccountOptions,
+        ReattachPolicy,
+    },
+    account_manager::operations::address_book::AddressBook,
     client::options::ClientOptions,
     signing::SignerType,
 };
@@ -24,8 +28,10 @@ use std::{
 pub struct AccountBuilder {
     client_options: Option<ClientOptions>,
     alias: Option<String>,
+    reattach_policy: ReattachPolicy,
     signer_type: SignerType,
     accounts: Arc<RwLock<Vec<AccountHandle>>>,
+    address_book: Arc<AddressBook>,
     #[cfg(feature = "events")]
     event_emitter: Arc<Mutex<EventEmitter>>,
 }
@@ -33,12 +39,18 @@ pub struct AccountBuilder {
 impl AccountBuilder {
     #[cfg(not(feature = "events"))]
     /// Create an IOTA client builder
-    pub fn new(accounts: Arc<RwLock<Vec<AccountHandle>>>, signer_type: SignerType) -> Self {
+    pub fn new(
+        accounts: Arc<RwLock<Vec<AccountHandle>>>,
+        signer_type: SignerType,
+        address_book: Arc<AddressBook>,
+    ) -> Self {
         Self {
             client_options: None,
             alias: None,
+            reattach_policy: ReattachPolicy::default(),
             signer_type,
             accounts,
+            address_book,
         }
     }
 
@@ -47,13 +59,16 @@ impl AccountBuilder {
     pub fn new(
         accounts: Arc<RwLock<Vec<AccountHandle>>>,
         signer_type: SignerType,
+        address_book: Arc<AddressBook>,
         event_emitter: Arc<Mutex<EventEmitter>>,
     ) -> Self {
         Self {
             client_options: None,
             alias: None,
+            reattach_policy: ReattachPolicy::default(),
             signer_type,
             accounts,
+            address_book,
             event_emitter,
         }
     }
@@ -63,6 +78,12 @@ impl AccountBuilder {
         self.alias.replace(alias);
         self
     }
+
+    /// Set the policy for reattaching pending transactions
+    pub fn with_reattach_policy(mut self, reattach_policy: ReattachPolicy) -> Self {
+        self.reattach_policy = reattach_policy;
+        self
+    }
     // Build the Account
     pub async fn finish(&self) -> crate::Result<AccountHandle> {
         let mut accounts = self.accounts.write().await;
@@ -84,19 +105,24 @@ impl AccountBuilder {
             addresses_with_balance: Vec::new(),
             outputs: HashMap::new(),
             locked_outputs: HashSet::new(),
+            frozen_outputs: HashSet::new(),
+            output_locks: HashMap::new(),
             unspent_outputs: HashMap::new(),
             transactions: HashMap::new(),
             pending_transactions: HashSet::new(),
+            idempotency_keys: HashMap::new(),
             // sync interval, output consolidation
             account_options: AccountOptions {
                 output_consolidation_threshold: consolidation_threshold,
                 automatic_output_consolidation: true,
+                reattach_policy: self.reattach_policy,
+                spending_policy: Default::default(),
             },
         };
         #[cfg(not(feature = "events"))]
-        let account_handle = AccountHandle::new(account);
+        let account_handle = AccountHandle::new(account, self.address_book.clone());
         #[cfg(feature = "events")]
-        let account_handle = AccountHandle::new(account, self.event_emitter.clone());
+        let account_handle = AccountHandle::new(account, self.address_book.clone(), self.event_emitter.clone());
         accounts.push(account_handle.clone());
         Ok(account_handle)
     }
@@ -1,21 +1,45 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use std::time::Duration;
+
 /// Amount at which outputs on a single addresses will get consolidated by default if consolidatioin is enabled
 pub(crate) const DEFAULT_OUTPUT_CONSOLIDATION_THRESHOLD: usize = 100;
 /// Amount at which outputs on a single addresses will get consolidated by default with a ledger signer if
 /// consolidatioin is enabled, needs to be smaller because the memory of the ledger nano s is limited
 pub(crate) const DEFAULT_LEDGER_OUTPUT_CONSOLIDATION_THRESHOLD: usize = 16;
 
-/// Amount of API request that can be sent in parallel during syncing
+/// Average time between two milestones in ms, used to estimate the timestamp of pruned milestones
+pub(crate) const MILESTONE_INTERVAL: u128 = 10000;
+
+/// Amount of API requests that are spawned together during syncing, how many of them are sent in parallel is limited
+/// by `ClientOptions::max_parallel_requests`
 pub(crate) const PARALLEL_REQUESTS_AMOUNT: usize = 500;
 
+/// How often a failed request during syncing is retried
+pub(crate) const SYNC_REQUEST_RETRIES: usize = 3;
+/// Delay before the first retry of a failed request during syncing, it's doubled for each further retry
+pub(crate) const SYNC_REQUEST_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 /// ms before an account actually syncs with the network, before it just returns the previous syncing result
 /// this is done to prevent unnecessary simultaneous synchronizations
 pub(crate) const MIN_SYNC_INTERVAL: u128 = 5000;
 
+/// Default time after which a pending transaction gets reattached
+pub(crate) const DEFAULT_REATTACH_DELAY: Duration = Duration::from_secs(30);
+
+/// Interval in which a transaction gets synced while waiting for its confirmation
+pub(crate) const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// ms after which an output lock gets released on startup, if the transaction using the output wasn't sent
+pub(crate) const OUTPUT_LOCK_TTL: u128 = 600000;
+
 /// The minimum amount of IOTA tokens an address with outputs needs to have
 pub(crate) const MIN_DUST_ALLOWANCE_VALUE: u64 = 1_000_000;
+/// The amount of dust allowance that allows an address to receive one dust output
+pub(crate) const DUST_ALLOWANCE_DIVISOR: u64 = 100_000;
+/// The maximum amount of dust outputs an address can have
+pub(crate) const MAX_DUST_OUTPUTS_ON_ADDRESS: usize = 100;
 
 /// The account prefix (do we really need this?)
 pub(crate) const ACCOUNT_ID_PREFIX: &str = "wallet-account://";
@@ -5,25 +5,44 @@ use crate::account::{
     operations::{
         address_generation,
         address_generation::AddressGenerationOptions,
+        annotations,
         balance_finder::search_addresses_with_funds,
-        syncing::{sync_account, SyncOptions},
-        transfer::{send_transfer, TransferOptions, TransferOutput, TransferResult},
+        confirmation::{self, TransactionConfirmation},
+        frozen_outputs, indexation, micropayments,
+        output_consolidation::{self, ConsolidationOptions, ConsolidationResult},
+        retry::{self, ReattachPolicy, RetryTasks},
+        spending_policy::{ReservedSpending, SpendingApprovalHandler, SpendingPolicy},
+        state_verification::{self, StateVerificationReport},
+        syncing::{sync_account, SyncOptions, SyncReport},
+        transfer::{
+            approval::{ApprovalDecision, ApprovalHook, PreparedTransaction},
+            idempotency::ReservedIdempotencyKeys,
+            replace_transaction::{cancel_transaction, replace_transaction},
+            send_transfer, TransferOptions, TransferOutput, TransferResult,
+        },
     },
     types::{
         address::{AccountAddress, AddressWithBalance},
-        AccountBalance, OutputData, Transaction,
+        AccountBalance, AddressDustAllowance, Annotation, OutputData, Transaction, TransactionFilter,
     },
     Account,
 };
+use crate::account_manager::operations::address_book::{self, AddressBook};
 #[cfg(feature = "events")]
 use crate::events::{
     types::{TransferProgressEvent, WalletEvent},
     EventEmitter,
 };
 
+use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId, Message, MessageId};
 use tokio::sync::{Mutex, RwLock};
 
-use std::{ops::Deref, sync::Arc};
+use std::{
+    future::Future,
+    ops::Deref,
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
 /// A thread guard over an account, so we can lock the account during operations.
 #[derive(Debug, Clone)]
@@ -33,6 +52,20 @@ pub struct AccountHandle {
     // if the last synced time was < `MIN_SYNC_INTERVAL` second ago, we don't sync, but only calculate the balance
     // again, because sending transactions can change that
     pub(crate) last_synced: Arc<Mutex<u128>>,
+    // request counts and duration of the latest syncing
+    pub(crate) last_sync_report: Arc<Mutex<SyncReport>>,
+    // background tasks to get sent transactions confirmed, they get aborted when the last handle is dropped
+    pub(crate) retry_tasks: Arc<RetryTasks>,
+    // approves outputs above the approval threshold of the spending policy
+    pub(crate) spending_approval_handler: Arc<Mutex<Option<SpendingApprovalHandler>>>,
+    // approves or rejects transactions before they get signed
+    pub(crate) approval_hook: Arc<Mutex<Option<ApprovalHook>>>,
+    // idempotency keys of transfers which are in progress
+    pub(crate) reserved_idempotency_keys: Arc<ReservedIdempotencyKeys>,
+    // amounts of transfers that passed the spending policy and aren't stored yet
+    pub(crate) reserved_spending: Arc<ReservedSpending>,
+    // the address book of the account manager
+    pub(crate) address_book: Arc<AddressBook>,
     #[cfg(feature = "events")]
     pub(crate) event_emitter: Arc<Mutex<EventEmitter>>,
 }
@@ -40,17 +73,35 @@ pub struct AccountHandle {
 impl AccountHandle {
     /// Create a new AccountHandle with an Account
     #[cfg(not(feature = "events"))]
-    pub(crate) fn new(account: Account) -> Self {
+    pub(crate) fn new(account: Account, address_book: Arc<AddressBook>) -> Self {
         Self {
             account: Arc::new(RwLock::new(account)),
             last_synced: Default::default(),
+            last_sync_report: Default::default(),
+            retry_tasks: Default::default(),
+            spending_approval_handler: Default::default(),
+            approval_hook: Default::default(),
+            reserved_idempotency_keys: Default::default(),
+            reserved_spending: Default::default(),
+            address_book,
         }
     }
     #[cfg(feature = "events")]
-    pub(crate) fn new(account: Account, event_emitter: Arc<Mutex<EventEmitter>>) -> Self {
+    pub(crate) fn new(
+        account: Account,
+        address_book: Arc<AddressBook>,
+        event_emitter: Arc<Mutex<EventEmitter>>,
+    ) -> Self {
         Self {
             account: Arc::new(RwLock::new(account)),
             last_synced: Default::default(),
+            last_sync_report: Default::default(),
+            retry_tasks: Default::default(),
+            spending_approval_handler: Default::default(),
+            approval_hook: Default::default(),
+            reserved_idempotency_keys: Default::default(),
+            reserved_spending: Default::default(),
+            address_book,
             event_emitter,
         }
     }
@@ -61,11 +112,59 @@ impl AccountHandle {
         sync_account(self, &options.unwrap_or_default()).await
     }
 
+    /// Returns the report of the latest syncing, with the amount of requests that were sent to the node
+    pub async fn last_sync_report(&self) -> SyncReport {
+        self.last_sync_report.lock().await.clone()
+    }
+
+    /// Compares the unspent outputs, locked outputs and pending transactions with the node and returns the
+    /// discrepancies, nothing is changed
+    pub async fn verify_state(&self) -> crate::Result<StateVerificationReport> {
+        state_verification::verify_state(self).await
+    }
+
+    /// Verifies the state and fixes the discrepancies, the repaired account is stored. Returns the repaired
+    /// discrepancies, outputs that couldn't be checked aren't changed.
+    pub async fn repair(&self) -> crate::Result<StateVerificationReport> {
+        state_verification::repair(self).await
+    }
+
     /// Consolidate outputs from addresses that have more outputs than the consolidation threshold
     async fn consolidate_outputs(&self) -> crate::Result<Vec<TransferResult>> {
         crate::account::operations::output_consolidation::consolidate_outputs(self).await
     }
 
+    /// Consolidates outputs of the account, with `dry_run` the consolidations are only returned without sending them.
+    /// Each result contains the result of its transaction, so a failed consolidation doesn't stop the other ones.
+    /// ```ignore
+    /// let planned = account_handle
+    ///     .consolidate(ConsolidationOptions {
+    ///         merge_addresses: true,
+    ///         min_outputs: Some(2),
+    ///         dry_run: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn consolidate(&self, options: ConsolidationOptions) -> crate::Result<Vec<ConsolidationResult>> {
+        output_consolidation::consolidate(self, options).await
+    }
+
+    /// Enables or disables the automatic output consolidation during syncing
+    pub async fn set_automatic_output_consolidation(&self, enabled: bool) -> crate::Result<()> {
+        let mut account = self.account.write().await;
+        account.account_options.automatic_output_consolidation = enabled;
+        #[cfg(feature = "storage")]
+        log::debug!("[OUTPUT_CONSOLIDATION] storing account {}", account.index());
+        crate::storage::manager::get()
+            .await?
+            .lock()
+            .await
+            .save_account(&account)
+            .await?;
+        Ok(())
+    }
+
     /// Send a transaction, if sending a message fails, the function will return None for the message_id, but the wallet
     /// will retry sending the transaction during syncing.
     /// ```ignore
@@ -116,10 +215,181 @@ impl AccountHandle {
         send_transfer(self, outputs, options).await
     }
 
-    // /// Reattaches or promotes a message to get it confirmed
-    // pub async fn retry(message_id: MessageId, sync: bool) -> crate::Result<MessageId> {
-    //     Ok(MessageId::from_str("")?)
-    // }
+    /// Sends a transaction and waits until it's confirmed or conflicting. If it's still pending after the timeout,
+    /// [`crate::Error::ConfirmationTimeout`] is returned, the transaction can still get confirmed later.
+    pub async fn send_and_wait(
+        &self,
+        outputs: Vec<TransferOutput>,
+        options: Option<TransferOptions>,
+        timeout: Duration,
+    ) -> crate::Result<TransactionConfirmation> {
+        let transfer_result = self.send(outputs, options).await?;
+        self.wait_for_confirmation(transfer_result.transaction_id, timeout)
+            .await
+    }
+
+    /// Waits until a transaction is confirmed or conflicting, the transaction gets synced in the meantime
+    pub async fn wait_for_confirmation(
+        &self,
+        transaction_id: TransactionId,
+        timeout: Duration,
+    ) -> crate::Result<TransactionConfirmation> {
+        confirmation::wait_for_confirmation(self, transaction_id, timeout).await
+    }
+
+    /// Cancel a pending transaction by sending its inputs back to an own address. The cancellation spends the same
+    /// inputs as the original transaction, so at most one of them will get confirmed, which one is tracked during
+    /// syncing.
+    pub async fn cancel_transaction(&self, transaction_id: TransactionId) -> crate::Result<TransferResult> {
+        cancel_transaction(self, transaction_id).await
+    }
+
+    /// Replace a pending transaction with a new one to other outputs, for example to correct a wrong recipient while
+    /// the original transaction is unconfirmed. The new transaction spends the same inputs, so at most one of them
+    /// will get confirmed.
+    pub async fn replace_transaction(
+        &self,
+        transaction_id: TransactionId,
+        outputs: Vec<TransferOutput>,
+    ) -> crate::Result<TransferResult> {
+        replace_transaction(self, transaction_id, outputs).await
+    }
+
+    /// Reattaches or promotes the message of a pending transaction to get it confirmed, depending on what the node
+    /// suggests. Returns the new message id, or the current one if neither is needed.
+    pub async fn retry(&self, transaction_id: TransactionId) -> crate::Result<MessageId> {
+        retry::retry(self, transaction_id).await
+    }
+
+    /// Reattaches a pending transaction in a new message and returns the new message id
+    pub async fn reattach(&self, transaction_id: TransactionId) -> crate::Result<MessageId> {
+        retry::reattach(self, transaction_id).await
+    }
+
+    /// Promotes the message of a pending transaction and returns the id of the promotion message
+    pub async fn promote(&self, transaction_id: TransactionId) -> crate::Result<MessageId> {
+        retry::promote(self, transaction_id).await
+    }
+
+    /// Sets the policy for reattaching pending transactions during syncing and after sending them
+    pub async fn set_reattach_policy(&self, reattach_policy: ReattachPolicy) -> crate::Result<()> {
+        let mut account = self.account.write().await;
+        account.account_options.reattach_policy = reattach_policy;
+        #[cfg(feature = "storage")]
+        log::debug!("[REATTACH POLICY] storing account {}", account.index());
+        crate::storage::manager::get()
+            .await?
+            .lock()
+            .await
+            .save_account(&account)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the spending policy of the account, which is checked before transactions get signed
+    pub async fn set_spending_policy(&self, spending_policy: SpendingPolicy) -> crate::Result<()> {
+        let mut account = self.account.write().await;
+        account.account_options.spending_policy = spending_policy;
+        #[cfg(feature = "storage")]
+        log::debug!("[SPENDING POLICY] storing account {}", account.index());
+        crate::storage::manager::get()
+            .await?
+            .lock()
+            .await
+            .save_account(&account)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the handler which approves outputs above the approval threshold of the spending policy, the outputs are
+    /// approved if it returns true. The handler isn't stored and needs to be set again after a restart.
+    pub async fn set_spending_approval_handler<F>(&self, handler: F)
+    where
+        F: Fn(&[TransferOutput]) -> bool + Send + Sync + 'static,
+    {
+        self.spending_approval_handler
+            .lock()
+            .await
+            .replace(SpendingApprovalHandler(Arc::new(handler)));
+    }
+
+    /// Sets an async hook which gets every transaction right before it's signed and can approve or reject it, inputs
+    /// of rejected transactions are unlocked again. The hook isn't stored and needs to be set again after a restart.
+    /// ```ignore
+    /// account_handle
+    ///     .set_approval_hook(|prepared_transaction| async move {
+    ///         if prepared_transaction.outputs.iter().any(|o| o.amount > 1_000_000_000) {
+    ///             ApprovalDecision::Reject("amount too high".to_string())
+    ///         } else {
+    ///             ApprovalDecision::Approve
+    ///         }
+    ///     })
+    ///     .await;
+    /// ```
+    pub async fn set_approval_hook<F, Fut>(&self, hook: F)
+    where
+        F: Fn(PreparedTransaction) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ApprovalDecision> + Send + 'static,
+    {
+        self.approval_hook.lock().await.replace(ApprovalHook::new(hook));
+    }
+
+    /// Removes the approval hook, transactions are signed without approval afterwards
+    pub async fn remove_approval_hook(&self) {
+        self.approval_hook.lock().await.take();
+    }
+
+    /// Freezes unspent outputs, frozen outputs are only spent if they're provided as `custom_inputs`
+    pub async fn freeze_outputs(&self, output_ids: Vec<OutputId>) -> crate::Result<()> {
+        frozen_outputs::freeze_outputs(self, output_ids).await
+    }
+
+    /// Unfreezes outputs, so they can be selected as inputs for transfers again
+    pub async fn unfreeze_outputs(&self, output_ids: Vec<OutputId>) -> crate::Result<()> {
+        frozen_outputs::unfreeze_outputs(self, output_ids).await
+    }
+
+    /// Returns the frozen unspent outputs of the account
+    pub async fn list_frozen_outputs(&self) -> crate::Result<Vec<OutputData>> {
+        let account = self.read().await;
+        let mut outputs = Vec::new();
+        for output_id in &account.frozen_outputs {
+            if let Some(output) = account.unspent_outputs.get(output_id) {
+                outputs.push(output.clone());
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Sends a message with an indexation payload without value, the data can be found with the index
+    /// ```ignore
+    /// let message_id = account_handle.send_data(b"PING", b"some data").await?;
+    /// let messages = account_handle.find_messages_by_index(b"PING").await?;
+    /// ```
+    pub async fn send_data(&self, index: &[u8], data: &[u8]) -> crate::Result<MessageId> {
+        indexation::send_data(self, index, data).await
+    }
+
+    /// Returns the messages with an indexation payload for an index
+    pub async fn find_messages_by_index(&self, index: &[u8]) -> crate::Result<Vec<Message>> {
+        indexation::find_messages_by_index(index).await
+    }
+
+    /// Enables micropayments for an address of the account by sending a dust allowance output with `allowance` to it,
+    /// this allows the address to receive outputs below 1 Mi
+    pub async fn enable_micropayments(&self, address: String, allowance: u64) -> crate::Result<TransferResult> {
+        micropayments::enable_micropayments(self, address, allowance).await
+    }
+
+    /// Disables micropayments for an address by sending its dust allowance and dust outputs to a normal output
+    pub async fn disable_micropayments(&self, address: String) -> crate::Result<TransferResult> {
+        micropayments::disable_micropayments(self, address).await
+    }
+
+    /// Returns the dust allowance and how many dust outputs each address with balance can still receive
+    pub async fn dust_allowance_status(&self) -> crate::Result<Vec<AddressDustAllowance>> {
+        micropayments::dust_allowance_status(self).await
+    }
 
     /// Generate addresses
     /// ```ignore
@@ -180,14 +450,38 @@ impl AccountHandle {
 
     /// Returns all transaction of the account
     pub async fn list_transactions(&self) -> crate::Result<Vec<Transaction>> {
+        self.list_transactions_filtered(TransactionFilter::default()).await
+    }
+
+    /// Returns the transactions of the account which match the filter
+    pub async fn list_transactions_filtered(&self, filter: TransactionFilter) -> crate::Result<Vec<Transaction>> {
         let account = self.read().await;
         let mut transactions = Vec::new();
         for transaction in account.transactions.values() {
-            transactions.push(transaction.clone());
+            if filter.matches(transaction) {
+                transactions.push(transaction.clone());
+            }
+        }
+        if let Some(address) = account.public_addresses.first() {
+            address_book::set_contact_names(&self.address_book, &mut transactions, &address.address.bech32_hrp).await;
         }
         Ok(transactions)
     }
 
+    /// Sets the note, label and tags of a transaction, they're only stored locally
+    pub async fn set_transaction_note(
+        &self,
+        transaction_id: TransactionId,
+        annotation: Annotation,
+    ) -> crate::Result<()> {
+        annotations::set_transaction_note(self, transaction_id, annotation).await
+    }
+
+    /// Sets the note, label and tags of an output, they're only stored locally
+    pub async fn set_output_note(&self, output_id: OutputId, annotation: Annotation) -> crate::Result<()> {
+        annotations::set_output_note(self, output_id, annotation).await
+    }
+
     /// Returns all pending transaction of the account
     pub async fn list_pending_transactions(&self) -> crate::Result<Vec<Transaction>> {
         let account = self.read().await;
@@ -197,35 +491,27 @@ impl AccountHandle {
                 transactions.push(transaction.clone());
             }
         }
+        if let Some(address) = account.public_addresses.first() {
+            address_book::set_contact_names(&self.address_book, &mut transactions, &address.address.bech32_hrp).await;
+        }
         Ok(transactions)
     }
 
     /// Get the total and available balance of an account
     pub async fn balance(&self) -> crate::Result<AccountBalance> {
         log::debug!("[BALANCE] get balance");
-        let account = self.account.read().await;
-        let total_balance: u64 = account.addresses_with_balance.iter().map(|a| a.balance()).sum();
-        // for `available` get locked_outputs, sum outputs balance and subtract from total_balance
-        log::debug!("[BALANCE] locked outputs: {:#?}", account.locked_outputs);
-        let mut locked_balance = 0;
         let client = crate::client::get_client().await?;
         let network_id = client.get_network_id().await?;
-        for locked_output in &account.locked_outputs {
-            if let Some(output) = account.unspent_outputs.get(locked_output) {
-                if output.network_id == network_id {
-                    locked_balance += output.amount;
-                }
-            }
-        }
+        let account = self.account.read().await;
+        log::debug!("[BALANCE] locked outputs: {:#?}", account.locked_outputs);
+        let balance = account.balance(network_id);
         log::debug!(
-            "[BALANCE] total_balance: {}, lockedbalance: {}",
-            total_balance,
-            locked_balance
+            "[BALANCE] total_balance: {}, available_balance: {}, frozen_balance: {}",
+            balance.total,
+            balance.available,
+            balance.frozen
         );
-        Ok(AccountBalance {
-            total: total_balance,
-            available: total_balance - locked_balance,
-        })
+        Ok(balance)
     }
 
     // Should only be called from the AccountManager so all accounts are on the same state
@@ -264,6 +550,58 @@ impl AccountHandle {
     pub(crate) async fn search_addresses_with_funds(&self, address_gap_limit: usize) -> crate::Result<AccountBalance> {
         search_addresses_with_funds(self, address_gap_limit).await
     }
+
+    /// Create a weak handle which doesn't keep the account alive, for background tasks
+    pub(crate) fn downgrade(&self) -> WeakAccountHandle {
+        WeakAccountHandle {
+            account: Arc::downgrade(&self.account),
+            last_synced: Arc::downgrade(&self.last_synced),
+            last_sync_report: Arc::downgrade(&self.last_sync_report),
+            retry_tasks: Arc::downgrade(&self.retry_tasks),
+            spending_approval_handler: Arc::downgrade(&self.spending_approval_handler),
+            approval_hook: Arc::downgrade(&self.approval_hook),
+            reserved_idempotency_keys: Arc::downgrade(&self.reserved_idempotency_keys),
+            reserved_spending: Arc::downgrade(&self.reserved_spending),
+            address_book: Arc::downgrade(&self.address_book),
+            #[cfg(feature = "events")]
+            event_emitter: Arc::downgrade(&self.event_emitter),
+        }
+    }
+}
+
+/// A handle to an account which doesn't keep it alive, created with [`AccountHandle::downgrade()`]
+#[derive(Debug, Clone)]
+pub(crate) struct WeakAccountHandle {
+    account: Weak<RwLock<Account>>,
+    last_synced: Weak<Mutex<u128>>,
+    last_sync_report: Weak<Mutex<SyncReport>>,
+    retry_tasks: Weak<RetryTasks>,
+    spending_approval_handler: Weak<Mutex<Option<SpendingApprovalHandler>>>,
+    approval_hook: Weak<Mutex<Option<ApprovalHook>>>,
+    reserved_idempotency_keys: Weak<ReservedIdempotencyKeys>,
+    reserved_spending: Weak<ReservedSpending>,
+    address_book: Weak<AddressBook>,
+    #[cfg(feature = "events")]
+    event_emitter: Weak<Mutex<EventEmitter>>,
+}
+
+impl WeakAccountHandle {
+    /// Get the account handle, if the account wasn't dropped yet
+    pub(crate) fn upgrade(&self) -> Option<AccountHandle> {
+        Some(AccountHandle {
+            account: self.account.upgrade()?,
+            last_synced: self.last_synced.upgrade()?,
+            last_sync_report: self.last_sync_report.upgrade()?,
+            retry_tasks: self.retry_tasks.upgrade()?,
+            spending_approval_handler: self.spending_approval_handler.upgrade()?,
+            approval_hook: self.approval_hook.upgrade()?,
+            reserved_idempotency_keys: self.reserved_idempotency_keys.upgrade()?,
+            reserved_spending: self.reserved_spending.upgrade()?,
+            address_book: self.address_book.upgrade()?,
+            #[cfg(feature = "events")]
+            event_emitter: self.event_emitter.upgrade()?,
+        })
+    }
 }
 
 // impl Deref so we can use `account_handle.read()` instead of `account_handle.account.read()`
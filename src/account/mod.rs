@@ -9,23 +9,35 @@ pub(crate) mod constants;
 pub(crate) mod handle;
 /// The account operations like address generation, syncing and creating transfers.
 pub(crate) mod operations;
+/// Accounts, outputs and transactions for unit tests.
+#[cfg(test)]
+pub(crate) mod test_utils;
 /// Types used in an account and returned from methods.
 pub mod types;
 pub use operations::{
     address_generation::AddressGenerationOptions,
-    transfer::{RemainderValueStrategy, TransferOptions, TransferOutput},
+    confirmation::TransactionConfirmation,
+    output_consolidation::{ConsolidationOptions, ConsolidationResult},
+    retry::ReattachPolicy,
+    spending_policy::SpendingPolicy,
+    state_verification::{OrphanedTransaction, StateVerificationReport},
+    syncing::{SyncCancellation, SyncPhase, SyncReport},
+    transfer::{ApprovalDecision, PreparedTransaction, RemainderValueStrategy, TransferOptions, TransferOutput},
 };
 
 use crate::{
-    account::types::{
-        address::{AccountAddress, AddressWithBalance},
-        AccountBalance, OutputData,
+    account::{
+        operations::transfer::idempotency::IdempotencyRecord,
+        types::{
+            address::{AccountAddress, AddressWithBalance},
+            AccountBalance, OutputData, OutputLock,
+        },
     },
     signing::SignerType,
 };
 
 use getset::{Getters, Setters};
-use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId};
+use iota_client::bee_message::{address::Address, output::OutputId, payload::transaction::TransactionId};
 use serde::{Deserialize, Serialize};
 
 use std::collections::{HashMap, HashSet};
@@ -53,6 +65,13 @@ pub struct Account {
     // outputs used in transactions should be locked here so they don't get used again, resulting in conflicting
     // transactions
     locked_outputs: HashSet<OutputId>,
+    // outputs frozen by the user, they're only spent if they're provided as custom inputs
+    #[serde(default)]
+    frozen_outputs: HashSet<OutputId>,
+    // journal with the transaction and expiration time for each locked output, it's stored separately from the account
+    // when the outputs get locked
+    #[serde(skip)]
+    output_locks: HashMap<OutputId, OutputLock>,
     // have unspent outputs in a separated hashmap so we don't need to iterate over all outputs we have
     unspent_outputs: HashMap<OutputId, OutputData>,
     // stored separated from the account for performance and only the transaction id here? where to add the network id?
@@ -60,16 +79,107 @@ pub struct Account {
     transactions: HashMap<TransactionId, types::Transaction>,
     // Maybe pending transactions even additionally separated?
     pending_transactions: HashSet<TransactionId>,
+    // transactions created for the idempotency keys of transfers
+    #[serde(default)]
+    idempotency_keys: HashMap<String, IdempotencyRecord>,
     // sync interval, output consolidation
     #[getset(get = "pub(crate)")]
     account_options: AccountOptions,
 }
 
+impl Account {
+    /// Returns true if the address is a public or internal address of the account
+    pub(crate) fn is_account_address(&self, address: &Address) -> bool {
+        self.get_account_address(address).is_some()
+    }
+
+    /// Returns the public or internal address of the account for the address
+    pub(crate) fn get_account_address(&self, address: &Address) -> Option<&AccountAddress> {
+        self.public_addresses
+            .iter()
+            .chain(self.internal_addresses.iter())
+            .find(|a| a.address.inner == *address)
+    }
+
+    /// The balance of the account on a network. Locked and frozen outputs aren't available, frozen outputs that are
+    /// locked only count as locked.
+    pub(crate) fn balance(&self, network_id: u64) -> AccountBalance {
+        let total: u64 = self.addresses_with_balance.iter().map(|a| a.balance()).sum();
+        let network_amount = |output_id: &OutputId| match self.unspent_outputs.get(output_id) {
+            Some(output) if output.network_id == network_id => output.amount,
+            _ => 0,
+        };
+        let locked: u64 = self.locked_outputs.iter().map(network_amount).sum();
+        let frozen: u64 = self
+            .frozen_outputs
+            .difference(&self.locked_outputs)
+            .map(network_amount)
+            .sum();
+        AccountBalance {
+            total,
+            available: total - locked - frozen,
+            frozen,
+        }
+    }
+}
+
 /// Account options
-#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub(crate) struct AccountOptions {
     pub(crate) output_consolidation_threshold: usize,
     pub(crate) automatic_output_consolidation: bool,
+    #[serde(default)]
+    pub(crate) reattach_policy: ReattachPolicy,
+    #[serde(default)]
+    pub(crate) spending_policy: SpendingPolicy,
     /* #[cfg(feature = "storage")]
      * pub(crate) persist_events: bool, */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        test_utils::{account, account_address, output, output_id},
+        types::address::AddressWithBalance,
+    };
+
+    #[test]
+    fn balance_with_locked_and_frozen_outputs() {
+        let mut account = account();
+        let address = account_address(1, 0, false);
+        let locked = output_id(1, 0);
+        let frozen = output_id(1, 1);
+        let frozen_and_locked = output_id(1, 2);
+        let available = output_id(1, 3);
+        let mut other_network = output(output_id(1, 4), address.address.inner, 5_000_000);
+        other_network.network_id = 1;
+        for (output_id, amount) in [
+            (locked, 1_000_000),
+            (frozen, 2_000_000),
+            (frozen_and_locked, 3_000_000),
+            (available, 4_000_000),
+        ] {
+            account
+                .unspent_outputs
+                .insert(output_id, output(output_id, address.address.inner, amount));
+        }
+        account.unspent_outputs.insert(other_network.output_id, other_network);
+        account.addresses_with_balance.push(AddressWithBalance {
+            address: address.address.clone(),
+            key_index: 0,
+            internal: false,
+            balance: 10_000_000,
+            output_ids: vec![locked, frozen, frozen_and_locked, available],
+        });
+        account.locked_outputs.extend([locked, frozen_and_locked]);
+        account
+            .frozen_outputs
+            .extend([frozen, frozen_and_locked, output_id(1, 4)]);
+
+        let balance = account.balance(0);
+        assert_eq!(balance.total, 10_000_000);
+        // frozen outputs that are locked and outputs of other networks don't count as frozen
+        assert_eq!(balance.frozen, 2_000_000);
+        assert_eq!(balance.available, 4_000_000);
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
     account::{
         handle::AccountHandle,
         types::address::{AccountAddress, AddressWrapper},
+        Account,
     },
     client,
     signing::{GenerateAddressMetadata, Network},
@@ -41,8 +42,6 @@ pub async fn generate_addresses(
 ) -> crate::Result<Vec<AccountAddress>> {
     log::debug!("[ADDRESS GENERATION] generating {} addresses", amount);
     let mut account = account_handle.write().await;
-    let signer = crate::signing::get_signer().await;
-    let mut signer = signer.lock().await;
 
     // get the highest index for the public or internal addresses
     let highest_current_index_plus_one = if options.internal {
@@ -50,6 +49,43 @@ pub async fn generate_addresses(
     } else {
         account.public_addresses.len()
     };
+    let generate_addresses = generate_account_addresses(
+        account_handle,
+        &account,
+        highest_current_index_plus_one,
+        amount,
+        &options,
+    )
+    .await?;
+
+    // add addresses to the account
+    if options.internal {
+        account.internal_addresses.extend(generate_addresses.clone());
+    } else {
+        account.public_addresses.extend(generate_addresses.clone());
+    };
+
+    #[cfg(feature = "storage")]
+    log::debug!("[ADDRESS GENERATION] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(generate_addresses)
+}
+
+/// Generate addresses of the account starting at `start_index`, without storing them
+pub(crate) async fn generate_account_addresses(
+    account_handle: &AccountHandle,
+    account: &Account,
+    start_index: usize,
+    amount: usize,
+    options: &AddressGenerationOptions,
+) -> crate::Result<Vec<AccountAddress>> {
+    let signer = crate::signing::get_signer().await;
+    let mut signer = signer.lock().await;
 
     // get bech32_hrp
     let bech32_hrp = {
@@ -66,7 +102,7 @@ pub async fn generate_addresses(
         }
     };
     let mut generate_addresses = Vec::new();
-    for address_index in highest_current_index_plus_one..highest_current_index_plus_one + amount {
+    for address_index in start_index..start_index + amount {
         #[cfg(all(feature = "events", any(feature = "ledger-nano", feature = "ledger-nano-simulator")))]
         // If we don't sync, then we want to display the prompt on the ledger with the address. But the user needs to
         // have it visible on the computer first, so we need to generate it without the prompt first
@@ -74,10 +110,10 @@ pub async fn generate_addresses(
             let mut changed_metadata = options.metadata.clone();
             changed_metadata.syncing = true;
             let address = signer
-                .generate_address(&account, address_index, options.internal, changed_metadata)
+                .generate_address(account, address_index, options.internal, changed_metadata)
                 .await?;
             let address_wrapper = AddressWrapper::new(address, bech32_hrp.clone());
-            self.event_emitter.lock().await.emit(
+            account_handle.event_emitter.lock().await.emit(
                 account.index,
                 WalletEvent::LedgerAddressGeneration(AddressData {
                     address: address_wrapper.to_bech32(),
@@ -86,7 +122,7 @@ pub async fn generate_addresses(
         }
 
         let address = signer
-            .generate_address(&account, address_index, options.internal, options.metadata.clone())
+            .generate_address(account, address_index, options.internal, options.metadata.clone())
             .await?;
 
         let address_wrapper = AddressWrapper::new(address, bech32_hrp.clone());
@@ -99,20 +135,5 @@ pub async fn generate_addresses(
         });
     }
 
-    // add addresses to the account
-    if options.internal {
-        account.internal_addresses.extend(generate_addresses.clone());
-    } else {
-        account.public_addresses.extend(generate_addresses.clone());
-    };
-
-    #[cfg(feature = "storage")]
-    log::debug!("[ADDRESS GENERATION] storing account {}", account.index());
-    crate::storage::manager::get()
-        .await?
-        .lock()
-        .await
-        .save_account(&account)
-        .await?;
     Ok(generate_addresses)
 }
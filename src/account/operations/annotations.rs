@@ -0,0 +1,57 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{handle::AccountHandle, types::Annotation};
+
+use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId};
+
+/// Sets the note, label and tags of a transaction, they're only stored locally
+pub(crate) async fn set_transaction_note(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+    annotation: Annotation,
+) -> crate::Result<()> {
+    log::debug!("[ANNOTATIONS] set note for transaction {}", transaction_id);
+    let mut account = account_handle.write().await;
+    account
+        .transactions
+        .get_mut(&transaction_id)
+        .ok_or(crate::Error::TransactionNotFound(transaction_id))?
+        .annotation = annotation;
+    #[cfg(feature = "storage")]
+    log::debug!("[ANNOTATIONS] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(())
+}
+
+/// Sets the note, label and tags of an output, they're only stored locally
+pub(crate) async fn set_output_note(
+    account_handle: &AccountHandle,
+    output_id: OutputId,
+    annotation: Annotation,
+) -> crate::Result<()> {
+    log::debug!("[ANNOTATIONS] set note for output {}", output_id);
+    let mut account = account_handle.write().await;
+    account
+        .outputs
+        .get_mut(&output_id)
+        .ok_or(crate::Error::OutputNotFound(output_id))?
+        .annotation = annotation.clone();
+    if let Some(output) = account.unspent_outputs.get_mut(&output_id) {
+        output.annotation = annotation;
+    }
+    #[cfg(feature = "storage")]
+    log::debug!("[ANNOTATIONS] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(())
+}
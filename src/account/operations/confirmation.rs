@@ -0,0 +1,117 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::CONFIRMATION_POLL_INTERVAL, handle::AccountHandle, operations::syncing::sync_pending_transaction,
+    types::InclusionState, Account,
+};
+
+use iota_client::bee_message::{payload::transaction::TransactionId, MessageId};
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+/// The final state of a transaction, returned from
+/// [`crate::account::handle::AccountHandle::wait_for_confirmation()`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionConfirmation {
+    /// The transaction id
+    #[serde(rename = "transactionId")]
+    pub transaction_id: TransactionId,
+    /// Confirmed or conflicting
+    #[serde(rename = "inclusionState")]
+    pub inclusion_state: InclusionState,
+    /// The message which included the transaction
+    #[serde(rename = "messageId")]
+    pub message_id: Option<MessageId>,
+}
+
+/// Syncs a transaction until it's confirmed or conflicting, returns [`crate::Error::ConfirmationTimeout`] if that
+/// doesn't happen within the timeout
+pub(crate) async fn wait_for_confirmation(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+    timeout: Duration,
+) -> crate::Result<TransactionConfirmation> {
+    log::debug!("[CONFIRMATION] wait for confirmation of {}", transaction_id);
+    let start_time = Instant::now();
+    loop {
+        if let Some(confirmation) = get_final_state(account_handle, &transaction_id).await? {
+            log::debug!(
+                "[CONFIRMATION] {} is {:?} after {:.2?}",
+                transaction_id,
+                confirmation.inclusion_state,
+                start_time.elapsed()
+            );
+            return Ok(confirmation);
+        }
+        let elapsed = start_time.elapsed();
+        if elapsed >= timeout {
+            return Err(crate::Error::ConfirmationTimeout(transaction_id));
+        }
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL.min(timeout - elapsed)).await;
+        // a failed request is retried with the next poll, until the timeout is reached
+        if let Err(e) = sync_pending_transaction(account_handle, transaction_id).await {
+            log::warn!("[CONFIRMATION] syncing {} failed: {}", transaction_id, e);
+        }
+    }
+}
+
+// Returns the confirmation if the transaction isn't pending anymore
+async fn get_final_state(
+    account_handle: &AccountHandle,
+    transaction_id: &TransactionId,
+) -> crate::Result<Option<TransactionConfirmation>> {
+    get_confirmation(&account_handle.read().await, transaction_id)
+}
+
+fn get_confirmation(
+    account: &Account,
+    transaction_id: &TransactionId,
+) -> crate::Result<Option<TransactionConfirmation>> {
+    let transaction = account
+        .transactions
+        .get(transaction_id)
+        .ok_or(crate::Error::TransactionNotFound(*transaction_id))?;
+    Ok(match transaction.inclusion_state {
+        InclusionState::Pending => None,
+        _ => Some(TransactionConfirmation {
+            transaction_id: *transaction_id,
+            inclusion_state: transaction.inclusion_state.clone(),
+            message_id: transaction.message_id,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_confirmation;
+    use crate::account::{
+        test_utils::{account, address, output_id, transaction},
+        types::InclusionState,
+    };
+
+    use iota_client::bee_message::MessageId;
+
+    #[test]
+    fn transaction_confirmation() {
+        let mut account = account();
+        let mut transaction = transaction(&[output_id(1, 0)], &[(address(1), 1_000_000)]);
+        transaction.message_id.replace(MessageId::new([1; 32]));
+        let transaction_id = transaction.payload.id();
+        assert!(matches!(
+            get_confirmation(&account, &transaction_id),
+            Err(crate::Error::TransactionNotFound(_))
+        ));
+
+        account.transactions.insert(transaction_id, transaction);
+        assert!(get_confirmation(&account, &transaction_id).unwrap().is_none());
+
+        for inclusion_state in [InclusionState::Confirmed, InclusionState::Conflicting] {
+            account.transactions.get_mut(&transaction_id).unwrap().inclusion_state = inclusion_state.clone();
+            let confirmation = get_confirmation(&account, &transaction_id).unwrap().unwrap();
+            assert_eq!(confirmation.inclusion_state, inclusion_state);
+            assert_eq!(confirmation.message_id, Some(MessageId::new([1; 32])));
+        }
+    }
+}
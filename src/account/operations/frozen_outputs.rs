@@ -0,0 +1,48 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::handle::AccountHandle;
+
+use iota_client::bee_message::output::OutputId;
+
+/// Freezes unspent outputs, so they don't get used by the automatic input selection and the output consolidation.
+/// They can still be spent with `custom_inputs`.
+pub(crate) async fn freeze_outputs(account_handle: &AccountHandle, output_ids: Vec<OutputId>) -> crate::Result<()> {
+    log::debug!("[FREEZE] freeze outputs {:?}", output_ids);
+    let mut account = account_handle.write().await;
+    // validate all outputs first, so we don't freeze only some of them
+    for output_id in &output_ids {
+        if !account.unspent_outputs.contains_key(output_id) {
+            return Err(crate::Error::OutputNotFound(*output_id));
+        }
+    }
+    account.frozen_outputs.extend(output_ids);
+    #[cfg(feature = "storage")]
+    log::debug!("[FREEZE] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(())
+}
+
+/// Unfreezes outputs, so they are available for the automatic input selection again. Outputs that aren't frozen are
+/// ignored.
+pub(crate) async fn unfreeze_outputs(account_handle: &AccountHandle, output_ids: Vec<OutputId>) -> crate::Result<()> {
+    log::debug!("[FREEZE] unfreeze outputs {:?}", output_ids);
+    let mut account = account_handle.write().await;
+    for output_id in &output_ids {
+        account.frozen_outputs.remove(output_id);
+    }
+    #[cfg(feature = "storage")]
+    log::debug!("[FREEZE] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(())
+}
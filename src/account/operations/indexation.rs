@@ -0,0 +1,54 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::PARALLEL_REQUESTS_AMOUNT,
+    handle::AccountHandle,
+    operations::{syncing::requests::request_with_retries, transfer::submit_transaction::submit_payload},
+};
+
+use iota_client::bee_message::{
+    payload::{indexation::IndexationPayload, Payload},
+    Message, MessageId,
+};
+
+/// Sends a message with an indexation payload and without value, returns the message id
+pub(crate) async fn send_data(account_handle: &AccountHandle, index: &[u8], data: &[u8]) -> crate::Result<MessageId> {
+    log::debug!("[INDEXATION] send_data with index {}", hex::encode(index));
+    let indexation_payload = IndexationPayload::new(index, data)?;
+    submit_payload(account_handle, Payload::Indexation(Box::new(indexation_payload))).await
+}
+
+/// Returns the messages for an index which have an indexation payload, also if it's part of a transaction. The
+/// messages are requested in parallel, messages which can't be requested are skipped.
+pub(crate) async fn find_messages_by_index(index: &[u8]) -> crate::Result<Vec<Message>> {
+    log::debug!("[INDEXATION] find_messages_by_index {}", hex::encode(index));
+    let client = crate::client::get_client().await?;
+    let message_ids = request_with_retries(|| async { Ok(client.get_message().index(index).await?) }).await?;
+    let mut messages = Vec::new();
+    for message_ids_chunk in message_ids
+        .chunks(PARALLEL_REQUESTS_AMOUNT)
+        .map(|x: &[MessageId]| x.to_vec())
+    {
+        let mut tasks = Vec::new();
+        for message_id in message_ids_chunk {
+            let client = client.clone();
+            tasks.push(async move {
+                tokio::spawn(async move {
+                    let client = &client;
+                    let message =
+                        request_with_retries(|| async move { Ok(client.get_message().data(&message_id).await?) }).await;
+                    (message_id, message)
+                })
+                .await
+            });
+        }
+        for (message_id, message) in futures::future::try_join_all(tasks).await? {
+            match message {
+                Ok(message) => messages.push(message),
+                Err(e) => log::warn!("[INDEXATION] skipped message {}: {}", message_id, e),
+            }
+        }
+    }
+    Ok(messages)
+}
@@ -0,0 +1,236 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::{DUST_ALLOWANCE_DIVISOR, MAX_DUST_OUTPUTS_ON_ADDRESS, MIN_DUST_ALLOWANCE_VALUE},
+    handle::AccountHandle,
+    operations::transfer::{send_transfer, TransferOptions, TransferOutput, TransferResult},
+    types::{
+        address::{parse_bech32_address, AddressWrapper},
+        AddressDustAllowance, OutputData, OutputKind,
+    },
+    Account,
+};
+
+use iota_client::bee_message::output::OutputId;
+
+/// Enables micropayments for an address of the account by sending a dust allowance output with `allowance` to it
+pub(crate) async fn enable_micropayments(
+    account_handle: &AccountHandle,
+    address: String,
+    allowance: u64,
+) -> crate::Result<TransferResult> {
+    log::debug!(
+        "[MICROPAYMENTS] enable micropayments for {} with {}i",
+        address,
+        allowance
+    );
+    if allowance < MIN_DUST_ALLOWANCE_VALUE {
+        return Err(crate::Error::DustError(format!(
+            "dust allowance needs to be at least {}i",
+            MIN_DUST_ALLOWANCE_VALUE
+        )));
+    }
+    get_account_address(account_handle, &address).await?;
+    send_transfer(
+        account_handle,
+        vec![TransferOutput {
+            address,
+            amount: allowance,
+            output_kind: Some(OutputKind::SignatureLockedDustAllowance),
+        }],
+        Some(TransferOptions {
+            skip_own_address_warning: true,
+            ..Default::default()
+        }),
+    )
+    .await
+}
+
+/// Disables micropayments for an address of the account. The dust allowance outputs and all dust outputs of the
+/// address are sent to a single SignatureLockedSingle output on the same address, because the dust outputs can't stay
+/// without dust allowance.
+pub(crate) async fn disable_micropayments(
+    account_handle: &AccountHandle,
+    address: String,
+) -> crate::Result<TransferResult> {
+    log::debug!("[MICROPAYMENTS] disable micropayments for {}", address);
+    let address_wrapper = get_account_address(account_handle, &address).await?;
+    let inputs = get_micropayment_outputs(&account_handle.read().await, &address_wrapper)?;
+    let amount = inputs.iter().map(|o| o.amount).sum();
+    let inputs: Vec<OutputId> = inputs.iter().map(|o| o.output_id).collect();
+    send_transfer(
+        account_handle,
+        vec![TransferOutput {
+            address,
+            amount,
+            output_kind: Some(OutputKind::SignatureLockedSingle),
+        }],
+        Some(TransferOptions {
+            custom_inputs: Some(inputs),
+            skip_own_address_warning: true,
+            ..Default::default()
+        }),
+    )
+    .await
+}
+
+// Get the dust allowance outputs and the dust outputs of an address. They're sent to a single output when
+// micropayments get disabled, so their sum can't be dust itself.
+fn get_micropayment_outputs(account: &Account, address: &AddressWrapper) -> crate::Result<Vec<OutputData>> {
+    let mut dust_allowance_outputs = Vec::new();
+    let mut dust_outputs = Vec::new();
+    for output in account.unspent_outputs.values() {
+        if output.address != address.inner {
+            continue;
+        }
+        match output.kind {
+            OutputKind::SignatureLockedDustAllowance => dust_allowance_outputs.push(output.clone()),
+            OutputKind::SignatureLockedSingle if output.amount < MIN_DUST_ALLOWANCE_VALUE => {
+                dust_outputs.push(output.clone())
+            }
+            _ => {}
+        }
+    }
+    if dust_allowance_outputs.is_empty() {
+        return Err(crate::Error::DustError(format!(
+            "{} has no dust allowance output",
+            address.to_bech32()
+        )));
+    }
+    dust_allowance_outputs.extend(dust_outputs);
+    let amount: u64 = dust_allowance_outputs.iter().map(|o| o.amount).sum();
+    if amount < MIN_DUST_ALLOWANCE_VALUE {
+        return Err(crate::Error::DustError(format!(
+            "the dust allowance and dust outputs of {} only have {}i, which would be a dust output",
+            address.to_bech32(),
+            amount
+        )));
+    }
+    Ok(dust_allowance_outputs)
+}
+
+/// Returns the dust allowance and the amount of dust outputs for each address with balance, so it's known how many
+/// micropayments an address can still receive
+pub(crate) async fn dust_allowance_status(account_handle: &AccountHandle) -> crate::Result<Vec<AddressDustAllowance>> {
+    Ok(get_dust_allowance_status(&account_handle.read().await))
+}
+
+// Count the dust allowance and the dust outputs of the addresses with balance
+fn get_dust_allowance_status(account: &Account) -> Vec<AddressDustAllowance> {
+    let mut dust_allowance_status = Vec::new();
+    for address in &account.addresses_with_balance {
+        let mut dust_allowance = 0;
+        let mut dust_outputs = 0;
+        for output_id in &address.output_ids {
+            if let Some(output) = account.unspent_outputs.get(output_id) {
+                match output.kind {
+                    OutputKind::SignatureLockedDustAllowance => dust_allowance += output.amount,
+                    OutputKind::SignatureLockedSingle if output.amount < MIN_DUST_ALLOWANCE_VALUE => dust_outputs += 1,
+                    _ => {}
+                }
+            }
+        }
+        dust_allowance_status.push(AddressDustAllowance {
+            address: address.address.clone(),
+            dust_allowance,
+            dust_outputs,
+            remaining_dust_outputs: allowed_dust_outputs(dust_allowance).saturating_sub(dust_outputs),
+        });
+    }
+    dust_allowance_status
+}
+
+/// The amount of dust outputs an address with the dust allowance can have
+pub(crate) fn allowed_dust_outputs(dust_allowance: u64) -> usize {
+    ((dust_allowance / DUST_ALLOWANCE_DIVISOR) as usize).min(MAX_DUST_OUTPUTS_ON_ADDRESS)
+}
+
+// Parse a bech32 address and check that it belongs to the account
+async fn get_account_address(account_handle: &AccountHandle, address: &str) -> crate::Result<AddressWrapper> {
+    let address = parse_bech32_address(address)?;
+    if !account_handle.read().await.is_account_address(&address.inner) {
+        return Err(crate::Error::InvalidAddress);
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allowed_dust_outputs, get_dust_allowance_status, get_micropayment_outputs};
+    use crate::account::{
+        test_utils::{account, account_address, address, output, output_id},
+        types::{address::AddressWithBalance, OutputKind},
+    };
+
+    #[test]
+    fn allowed_dust_output_count() {
+        assert_eq!(allowed_dust_outputs(0), 0);
+        assert_eq!(allowed_dust_outputs(1_000_000), 10);
+        assert_eq!(allowed_dust_outputs(1_250_000), 12);
+        // an address can't have more than 100 dust outputs
+        assert_eq!(allowed_dust_outputs(1_000_000_000), 100);
+    }
+
+    #[test]
+    fn dust_allowance_status_count() {
+        let mut account = account();
+        let address = account_address(1, 0, false);
+        let mut dust_allowance = output(output_id(1, 0), address.address.inner, 1_000_000);
+        dust_allowance.kind = OutputKind::SignatureLockedDustAllowance;
+        let outputs = vec![
+            dust_allowance,
+            output(output_id(1, 1), address.address.inner, 1),
+            output(output_id(1, 2), address.address.inner, 999_999),
+            output(output_id(1, 3), address.address.inner, 1_000_000),
+        ];
+        account.addresses_with_balance.push(AddressWithBalance {
+            address: address.address.clone(),
+            key_index: 0,
+            internal: false,
+            balance: outputs.iter().map(|o| o.amount).sum(),
+            output_ids: outputs.iter().map(|o| o.output_id).collect(),
+        });
+        for output in outputs {
+            account.unspent_outputs.insert(output.output_id, output);
+        }
+
+        let status = get_dust_allowance_status(&account);
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].dust_allowance, 1_000_000);
+        assert_eq!(status[0].dust_outputs, 2);
+        assert_eq!(status[0].remaining_dust_outputs, 8);
+    }
+
+    #[test]
+    fn micropayment_outputs() {
+        let mut account = account();
+        let own_address = account_address(1, 0, false);
+        let dust = output(output_id(1, 1), own_address.address.inner, 1);
+        account.unspent_outputs.insert(dust.output_id, dust);
+        // micropayments can't be disabled without dust allowance
+        assert!(get_micropayment_outputs(&account, &own_address.address).is_err());
+
+        let mut dust_allowance = output(output_id(1, 0), own_address.address.inner, 1_000_000);
+        dust_allowance.kind = OutputKind::SignatureLockedDustAllowance;
+        account.unspent_outputs.insert(dust_allowance.output_id, dust_allowance);
+        let other_address = output(output_id(1, 2), address(2), 1);
+        account.unspent_outputs.insert(other_address.output_id, other_address);
+        let no_dust = output(output_id(1, 3), own_address.address.inner, 1_000_000);
+        account.unspent_outputs.insert(no_dust.output_id, no_dust);
+        let mut outputs: Vec<u64> = get_micropayment_outputs(&account, &own_address.address)
+            .unwrap()
+            .iter()
+            .map(|o| o.amount)
+            .collect();
+        outputs.sort_unstable();
+        assert_eq!(outputs, vec![1, 1_000_000]);
+
+        // the sum is sent to a single output, which can't be dust
+        account.unspent_outputs.get_mut(&output_id(1, 0)).unwrap().amount = 999_998;
+        assert!(matches!(
+            get_micropayment_outputs(&account, &own_address.address),
+            Err(crate::Error::DustError(_))
+        ));
+    }
+}
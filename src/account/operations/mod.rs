@@ -3,10 +3,31 @@
 
 /// The module for the address generation
 pub(crate) mod address_generation;
+/// The module for local notes, labels and tags of transactions and outputs
+pub(crate) mod annotations;
 /// The module to find additional addresses with balance
 pub(crate) mod balance_finder;
+/// The module to wait for the confirmation of transactions
+pub(crate) mod confirmation;
+/// The module to freeze outputs, so they only get spent as custom inputs
+pub(crate) mod frozen_outputs;
+/// The module to send and find data messages with an indexation payload
+pub(crate) mod indexation;
+/// The module to enable and disable micropayments with dust allowance outputs
+pub(crate) mod micropayments;
+/// The module to update an account with events from MQTT subscriptions
+#[cfg(feature = "mqtt")]
+pub(crate) mod mqtt;
 /// The module for the output consolidation
 pub(crate) mod output_consolidation;
+/// The module for the output lock journal
+pub(crate) mod output_locks;
+/// The module to reattach and promote pending transactions
+pub(crate) mod retry;
+/// The module for the spending limits of an account
+pub(crate) mod spending_policy;
+/// The module to compare the local state of an account with the ledger and repair it
+pub(crate) mod state_verification;
 /// The module for synchronization of an account
 pub(crate) mod syncing;
 /// The module for value transfers
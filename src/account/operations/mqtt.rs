@@ -0,0 +1,166 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    handle::AccountHandle,
+    operations::{
+        output_locks::save_output_locks,
+        state_verification::remove_unspent_output,
+        syncing::{
+            incoming_transactions::get_incoming_transactions,
+            outputs::{get_output_amount_and_address, output_response_to_output_data},
+            sync_pending_transaction, SyncReport,
+        },
+    },
+    types::address::AddressWithBalance,
+};
+#[cfg(feature = "events")]
+use crate::events::types::{BalanceChangeEvent, IncomingTransactionEvent, WalletEvent};
+
+use iota_client::{
+    bee_message::MessageId,
+    bee_rest_api::types::responses::{MessageMetadataResponse, OutputResponse},
+};
+
+/// Update the account with an output from an `addresses/{address}/outputs` event, returns false if the address of the
+/// output doesn't belong to the account
+pub(crate) async fn handle_output_event(
+    account_handle: &AccountHandle,
+    output_response: OutputResponse,
+) -> crate::Result<bool> {
+    let (_, address, _) = get_output_amount_and_address(&output_response.output)?;
+    let account_address = account_handle.read().await.get_account_address(&address).cloned();
+    let account_address = match account_address {
+        Some(account_address) => account_address,
+        None => return Ok(false),
+    };
+    let mut report = SyncReport::default();
+    let output = output_response_to_output_data(account_handle, vec![output_response], &mut report)
+        .await?
+        .pop()
+        .expect("one output converted");
+    log::debug!("[MQTT] output event for {:?}", output.output_id);
+    let incoming_transactions = if output.is_spent {
+        Vec::new()
+    } else {
+        get_incoming_transactions(account_handle, &[output.clone()], &mut report).await?
+    };
+
+    let mut account = account_handle.write().await;
+    let known_unspent = account.unspent_outputs.contains_key(&output.output_id);
+    let balance_change = match (output.is_spent, known_unspent) {
+        // nothing changed since the latest syncing or event
+        (false, true) => return Ok(true),
+        (true, false) => {
+            if !account.outputs.contains_key(&output.output_id) {
+                account.outputs.insert(output.output_id, output);
+            }
+            return Ok(true);
+        }
+        (false, false) => output.amount as i64,
+        (true, true) => -(output.amount as i64),
+    };
+    log::debug!(
+        "[MQTT] balance change {} on {}",
+        balance_change,
+        account_address.address.to_bech32()
+    );
+
+    if output.is_spent {
+        remove_unspent_output(&mut account, &output.output_id);
+    } else {
+        let position = account
+            .addresses_with_balance
+            .iter()
+            .position(|a| a.address.inner == address);
+        match position {
+            Some(position) => {
+                let address_with_balance = &mut account.addresses_with_balance[position];
+                address_with_balance.balance += output.amount;
+                address_with_balance.output_ids.push(output.output_id);
+            }
+            None => account.addresses_with_balance.push(AddressWithBalance {
+                address: account_address.address.clone(),
+                key_index: account_address.key_index,
+                internal: account_address.internal,
+                balance: output.amount,
+                output_ids: vec![output.output_id],
+            }),
+        }
+        let addresses = if account_address.internal {
+            &mut account.internal_addresses
+        } else {
+            &mut account.public_addresses
+        };
+        if let Some(address) = addresses.iter_mut().find(|a| a.key_index == account_address.key_index) {
+            address.used = true;
+        }
+        account.unspent_outputs.insert(output.output_id, output.clone());
+    }
+    account.outputs.insert(output.output_id, output);
+    for transaction in incoming_transactions {
+        #[cfg(feature = "events")]
+        account_handle.event_emitter.lock().await.emit(
+            account.index,
+            WalletEvent::IncomingTransaction(IncomingTransactionEvent {
+                transaction_id: transaction.payload.id(),
+                amount: transaction.received_amount.unwrap_or_default(),
+                senders: transaction.senders.clone(),
+            }),
+        );
+        account.transactions.insert(transaction.payload.id(), transaction);
+    }
+
+    #[cfg(feature = "events")]
+    {
+        let new_balance = account.addresses_with_balance.iter().map(|a| a.balance).sum();
+        account_handle.event_emitter.lock().await.emit(
+            account.index,
+            WalletEvent::BalanceChange(BalanceChangeEvent {
+                address: account_address.address,
+                balance_change,
+                new_balance,
+            }),
+        );
+    }
+    #[cfg(feature = "storage")]
+    log::debug!("[MQTT] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    save_output_locks(&account).await?;
+    Ok(true)
+}
+
+/// Sync the pending transaction of a message from a `messages/{messageId}/metadata` event, once the message is
+/// referenced by a milestone
+pub(crate) async fn handle_metadata_event(
+    account_handle: &AccountHandle,
+    message_id: MessageId,
+    metadata: MessageMetadataResponse,
+) -> crate::Result<()> {
+    if metadata.ledger_inclusion_state.is_none() {
+        return Ok(());
+    }
+    let transaction_id = {
+        let account = account_handle.read().await;
+        account.pending_transactions.iter().copied().find(|transaction_id| {
+            account
+                .transactions
+                .get(transaction_id)
+                .map_or(false, |transaction| transaction.message_id == Some(message_id))
+        })
+    };
+    if let Some(transaction_id) = transaction_id {
+        log::debug!(
+            "[MQTT] message {} of transaction {} got referenced",
+            message_id,
+            transaction_id
+        );
+        sync_pending_transaction(account_handle, transaction_id).await?;
+    }
+    Ok(())
+}
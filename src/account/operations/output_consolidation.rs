@@ -2,13 +2,20 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::account::{
+    constants::MIN_DUST_ALLOWANCE_VALUE,
     handle::AccountHandle,
     operations::transfer::{send_transfer, TransferResult},
-    types::{address::AddressWithBalance, OutputData, OutputKind},
-    TransferOptions, TransferOutput,
+    types::{
+        address::{parse_bech32_address, AddressWithBalance},
+        OutputData, OutputKind,
+    },
+    Account, TransferOptions, TransferOutput,
 };
 
-use iota_client::bee_message::output::OutputId;
+use iota_client::bee_message::{constants::INPUT_OUTPUT_COUNT_MAX, output::OutputId};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
 
 /// Consolidates outputs from an account by sending them to the same address again if the output amount is >= the
 /// output_consolidation_threshold
@@ -28,16 +35,22 @@ pub(crate) async fn consolidate_outputs(account_handle: &AccountHandle) -> crate
     log::debug!("[OUTPUT_CONSOLIDATION] consolidating outputs if needed");
     let client = crate::client::get_client().await?;
     let bech32_hrp = client.get_bech32_hrp().await?;
-    // Get outputs for the consoldation
-    let mut outputs_to_consolidate: Vec<Vec<OutputData>> = Vec::new();
+    // Get outputs for the consoldation, with the dust allowance outputs of the address
+    let mut outputs_to_consolidate: Vec<(Vec<OutputData>, Vec<OutputData>)> = Vec::new();
     for address in addresses_that_need_consolidation {
         let mut unspent_outputs = Vec::new();
+        let mut dust_allowance_outputs = Vec::new();
         for output_id in &address.output_ids {
-            if !account.locked_outputs.contains(output_id) {
+            if !account.locked_outputs.contains(output_id) && !account.frozen_outputs.contains(output_id) {
                 if let Some(output) = account.outputs.get(output_id) {
-                    // only consolidate SignatureLockedSingle outputs so we can't get problems with the dust protection
-                    if !output.is_spent && output.kind == OutputKind::SignatureLockedSingle {
-                        unspent_outputs.push(output.clone());
+                    // dust allowance outputs are only consolidated together with all dust outputs, so we can't get
+                    // problems with the dust protection
+                    if !output.is_spent {
+                        match output.kind {
+                            OutputKind::SignatureLockedSingle => unspent_outputs.push(output.clone()),
+                            OutputKind::SignatureLockedDustAllowance => dust_allowance_outputs.push(output.clone()),
+                            _ => {}
+                        }
                     }
                 }
             }
@@ -49,7 +62,7 @@ pub(crate) async fn consolidate_outputs(account_handle: &AccountHandle) -> crate
                 address.address.to_bech32(),
                 unspent_outputs.len()
             );
-            outputs_to_consolidate.push(unspent_outputs);
+            outputs_to_consolidate.push((unspent_outputs, dust_allowance_outputs));
         }
     }
     drop(account);
@@ -58,26 +71,27 @@ pub(crate) async fn consolidate_outputs(account_handle: &AccountHandle) -> crate
         log::debug!("[OUTPUT_CONSOLIDATION] no consolidation needed");
     }
     let mut consolidation_results = Vec::new();
-    for outputs_on_one_address in outputs_to_consolidate {
+    for (outputs_on_one_address, dust_allowance_outputs) in outputs_to_consolidate {
+        // if all dust outputs of the address get swept in one transaction, the dust allowance outputs are merged into a
+        // single dust allowance output with them, so the address can still receive micropayments
+        let sweeps_all_dust = outputs_on_one_address.len() <= output_consolidation_threshold
+            && outputs_on_one_address
+                .iter()
+                .any(|o| o.amount < MIN_DUST_ALLOWANCE_VALUE)
+            && !dust_allowance_outputs.is_empty()
+            && outputs_on_one_address.len() + dust_allowance_outputs.len() <= INPUT_OUTPUT_COUNT_MAX;
         for outputs in outputs_on_one_address.chunks(output_consolidation_threshold) {
-            let output_sum = outputs.iter().map(|o| o.amount).sum();
-            let consolidation_output = vec![TransferOutput {
-                // use the address from the input for the output
-                address: outputs[0].address.to_bech32(&bech32_hrp),
-                amount: output_sum,
-                output_kind: None,
-            }];
-            match send_transfer(
-                account_handle,
-                consolidation_output,
-                Some(TransferOptions {
-                    skip_sync: true,
-                    custom_inputs: Some(outputs.iter().map(|o| o.output_id).collect::<Vec<OutputId>>()),
-                    ..Default::default()
-                }),
-            )
-            .await
-            {
+            let mut outputs = outputs.to_vec();
+            let mut output_kind = None;
+            if sweeps_all_dust {
+                outputs.extend(dust_allowance_outputs.iter().cloned());
+                output_kind.replace(OutputKind::SignatureLockedDustAllowance);
+            }
+            // use the address from the input for the output
+            let address = outputs[0].address.to_bech32(&bech32_hrp);
+            let inputs: Vec<OutputId> = outputs.iter().map(|o| o.output_id).collect();
+            let amount = outputs.iter().map(|o| o.amount).sum();
+            match send_consolidation(account_handle, &inputs, amount, address, output_kind).await {
                 Ok(res) => {
                     log::debug!(
                         "[OUTPUT_CONSOLIDATION] Consolidation transaction sent: msg_id: {:?} tx_id: {:?}",
@@ -93,3 +107,303 @@ pub(crate) async fn consolidate_outputs(account_handle: &AccountHandle) -> crate
 
     Ok(consolidation_results)
 }
+
+/// Options for [`crate::account::handle::AccountHandle::consolidate()`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsolidationOptions {
+    /// Bech32 address of the account that receives the consolidated outputs, by default outputs are sent back to
+    /// their address, or to the first address of the account if `merge_addresses` is set
+    #[serde(rename = "targetAddress", default)]
+    pub target_address: Option<String>,
+    /// Consolidate the outputs of all addresses together instead of each address on its own
+    #[serde(rename = "mergeAddresses", default)]
+    pub merge_addresses: bool,
+    /// Minimum amount of outputs an address (or all addresses with `merge_addresses`) needs to have, by default the
+    /// output consolidation threshold of the account
+    #[serde(rename = "minOutputs", default)]
+    pub min_outputs: Option<usize>,
+    /// Also consolidate dust allowance outputs, the consolidated output is a dust allowance output then
+    #[serde(rename = "includeDustAllowance", default)]
+    pub include_dust_allowance: bool,
+    /// Only return the planned consolidations without sending them
+    #[serde(rename = "dryRun", default)]
+    pub dry_run: bool,
+}
+
+/// A planned or sent consolidation transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidationResult {
+    /// The outputs that get consolidated
+    pub inputs: Vec<OutputId>,
+    /// The bech32 address that receives the consolidated output
+    pub address: String,
+    /// The amount of the consolidated output
+    pub amount: u64,
+    /// The kind of the consolidated output
+    #[serde(rename = "outputKind")]
+    pub output_kind: OutputKind,
+    /// The sent transaction, `None` for a dry run or if sending it failed
+    pub transfer: Option<TransferResult>,
+    /// Why sending the transaction failed
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Consolidates the unlocked and not frozen outputs of the account with the provided options. Errors of the single
+/// consolidation transactions are returned in the results.
+pub(crate) async fn consolidate(
+    account_handle: &AccountHandle,
+    options: ConsolidationOptions,
+) -> crate::Result<Vec<ConsolidationResult>> {
+    log::debug!("[OUTPUT_CONSOLIDATION] consolidate with {:?}", options);
+    let client = crate::client::get_client().await?;
+    let bech32_hrp = client.get_bech32_hrp().await?;
+    let network_id = client.get_network_id().await?;
+    let mut consolidation_results =
+        get_consolidations(&account_handle.read().await, &options, &bech32_hrp, network_id)?;
+    if options.dry_run {
+        return Ok(consolidation_results);
+    }
+    for consolidation in consolidation_results.iter_mut() {
+        match send_consolidation(
+            account_handle,
+            &consolidation.inputs,
+            consolidation.amount,
+            consolidation.address.clone(),
+            Some(consolidation.output_kind.clone()),
+        )
+        .await
+        {
+            Ok(transfer) => consolidation.transfer = Some(transfer),
+            Err(e) => consolidation.error = Some(e.to_string()),
+        }
+    }
+    Ok(consolidation_results)
+}
+
+// Plan the consolidation transactions with the options, without sending them
+fn get_consolidations(
+    account: &Account,
+    options: &ConsolidationOptions,
+    bech32_hrp: &str,
+    network_id: u64,
+) -> crate::Result<Vec<ConsolidationResult>> {
+    let output_consolidation_threshold = account.account_options.output_consolidation_threshold;
+    let min_outputs = options.min_outputs.unwrap_or(output_consolidation_threshold);
+    // the threshold also limits the inputs per transaction, like for the automatic consolidation
+    let max_inputs = output_consolidation_threshold.min(INPUT_OUTPUT_COUNT_MAX).max(2);
+
+    if let Some(target_address) = &options.target_address {
+        let target_address = parse_bech32_address(target_address)?;
+        if !account.is_account_address(&target_address.inner) {
+            return Err(crate::Error::InvalidAddress);
+        }
+    }
+
+    // outputs grouped by the address that receives them
+    let mut groups: HashMap<String, Vec<OutputData>> = HashMap::new();
+    for output in account.unspent_outputs.values() {
+        if output.is_spent
+            || output.network_id != network_id
+            || account.locked_outputs.contains(&output.output_id)
+            || account.frozen_outputs.contains(&output.output_id)
+        {
+            continue;
+        }
+        match output.kind {
+            OutputKind::SignatureLockedSingle => {}
+            OutputKind::SignatureLockedDustAllowance if options.include_dust_allowance => {}
+            _ => continue,
+        }
+        let address = match (&options.target_address, options.merge_addresses) {
+            (Some(target_address), _) => target_address.clone(),
+            (None, true) => account
+                .public_addresses
+                .first()
+                .expect("account has no address")
+                .address
+                .to_bech32(),
+            (None, false) => output.address.to_bech32(bech32_hrp),
+        };
+        groups.entry(address).or_default().push(output.clone());
+    }
+
+    let mut consolidations = Vec::new();
+    for (address, mut outputs) in groups {
+        if outputs.len() < min_outputs {
+            continue;
+        }
+        // biggest outputs first and the dust allowance outputs last, so the dust outputs are consolidated before or
+        // in the same transaction as the dust allowance which covers them
+        outputs.sort_by(|a, b| {
+            (a.kind == OutputKind::SignatureLockedDustAllowance)
+                .cmp(&(b.kind == OutputKind::SignatureLockedDustAllowance))
+                .then(b.amount.cmp(&a.amount))
+        });
+        for outputs in outputs.chunks(max_inputs) {
+            // a single output on the same address doesn't need to be consolidated
+            if outputs.len() < 2 && outputs[0].address.to_bech32(bech32_hrp) == address {
+                continue;
+            }
+            let amount: u64 = outputs.iter().map(|o| o.amount).sum();
+            // the consolidated output would be dust itself
+            if amount < MIN_DUST_ALLOWANCE_VALUE {
+                log::debug!(
+                    "[OUTPUT_CONSOLIDATION] skip {} outputs with {}i for {}, the consolidated output would be dust",
+                    outputs.len(),
+                    amount,
+                    address
+                );
+                continue;
+            }
+            let output_kind = if outputs
+                .iter()
+                .any(|o| o.kind == OutputKind::SignatureLockedDustAllowance)
+            {
+                OutputKind::SignatureLockedDustAllowance
+            } else {
+                OutputKind::SignatureLockedSingle
+            };
+            consolidations.push(ConsolidationResult {
+                inputs: outputs.iter().map(|o| o.output_id).collect(),
+                address: address.clone(),
+                amount,
+                output_kind,
+                transfer: None,
+                error: None,
+            });
+        }
+    }
+    Ok(consolidations)
+}
+
+// Send the outputs in a single output to the address
+async fn send_consolidation(
+    account_handle: &AccountHandle,
+    inputs: &[OutputId],
+    amount: u64,
+    address: String,
+    output_kind: Option<OutputKind>,
+) -> crate::Result<TransferResult> {
+    send_transfer(
+        account_handle,
+        vec![TransferOutput {
+            address,
+            amount,
+            output_kind,
+        }],
+        Some(TransferOptions {
+            skip_sync: true,
+            custom_inputs: Some(inputs.to_vec()),
+            skip_own_address_warning: true,
+            ..Default::default()
+        }),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_consolidations, ConsolidationOptions};
+    use crate::account::{
+        test_utils::{account, account_address, output, output_id},
+        types::OutputKind,
+    };
+
+    #[test]
+    fn consolidation_groups() {
+        let mut account = account();
+        account.account_options.output_consolidation_threshold = 2;
+        let first_address = account_address(1, 0, false);
+        let second_address = account_address(2, 1, false);
+        for (output_id, address) in [
+            (output_id(1, 0), &first_address),
+            (output_id(1, 1), &first_address),
+            (output_id(1, 2), &second_address),
+            (output_id(1, 3), &second_address),
+        ] {
+            account
+                .unspent_outputs
+                .insert(output_id, output(output_id, address.address.inner, 1_000_000));
+        }
+        account.public_addresses = vec![first_address.clone(), second_address.clone()];
+
+        // the outputs of each address are sent back to it
+        let consolidations = get_consolidations(&account, &ConsolidationOptions::default(), "atoi", 0).unwrap();
+        assert_eq!(consolidations.len(), 2);
+        for consolidation in consolidations {
+            assert_eq!(consolidation.inputs.len(), 2);
+            assert_eq!(consolidation.amount, 2_000_000);
+            assert!(consolidation.inputs.iter().all(|output_id| {
+                account.unspent_outputs[output_id].address.to_bech32("atoi") == consolidation.address
+            }));
+        }
+
+        // merged outputs are sent to the first address, or to the target address
+        let options = ConsolidationOptions {
+            merge_addresses: true,
+            min_outputs: Some(4),
+            ..Default::default()
+        };
+        let consolidations = get_consolidations(&account, &options, "atoi", 0).unwrap();
+        assert_eq!(consolidations.len(), 2);
+        assert!(consolidations
+            .iter()
+            .all(|consolidation| consolidation.address == first_address.address.to_bech32()));
+        let options = ConsolidationOptions {
+            target_address: Some(second_address.address.to_bech32()),
+            ..Default::default()
+        };
+        let consolidations = get_consolidations(&account, &options, "atoi", 0).unwrap();
+        assert_eq!(consolidations.len(), 2);
+        assert!(consolidations
+            .iter()
+            .all(|consolidation| consolidation.address == second_address.address.to_bech32()));
+        // the target address needs to belong to the account
+        let options = ConsolidationOptions {
+            target_address: Some(account_address(3, 0, false).address.to_bech32()),
+            ..Default::default()
+        };
+        assert!(get_consolidations(&account, &options, "atoi", 0).is_err());
+    }
+
+    #[test]
+    fn consolidation_order_and_dust() {
+        let mut account = account();
+        account.account_options.output_consolidation_threshold = 2;
+        let address = account_address(1, 0, false);
+        let mut dust_allowance = output(output_id(1, 0), address.address.inner, 1_000_000);
+        dust_allowance.kind = OutputKind::SignatureLockedDustAllowance;
+        for output in [
+            dust_allowance,
+            output(output_id(1, 1), address.address.inner, 5_000_000),
+            output(output_id(1, 2), address.address.inner, 1),
+            output(output_id(1, 3), address.address.inner, 3_000_000),
+        ] {
+            account.unspent_outputs.insert(output.output_id, output);
+        }
+        account.public_addresses = vec![address];
+
+        let options = ConsolidationOptions {
+            include_dust_allowance: true,
+            ..Default::default()
+        };
+        let mut consolidations = get_consolidations(&account, &options, "atoi", 0).unwrap();
+        consolidations.sort_by_key(|consolidation| consolidation.amount);
+        // the biggest outputs first, the dust allowance last together with the dust output
+        assert_eq!(consolidations.len(), 2);
+        assert_eq!(consolidations[0].inputs, vec![output_id(1, 2), output_id(1, 0)]);
+        assert_eq!(consolidations[0].output_kind, OutputKind::SignatureLockedDustAllowance);
+        assert_eq!(consolidations[1].inputs, vec![output_id(1, 1), output_id(1, 3)]);
+        assert_eq!(consolidations[1].output_kind, OutputKind::SignatureLockedSingle);
+
+        // dust outputs aren't consolidated if the consolidated output would be dust too
+        let options = ConsolidationOptions::default();
+        account.unspent_outputs.remove(&output_id(1, 0));
+        account.unspent_outputs.remove(&output_id(1, 1));
+        account.unspent_outputs.remove(&output_id(1, 3));
+        let dust = output(output_id(1, 4), account.public_addresses[0].address.inner, 2);
+        account.unspent_outputs.insert(dust.output_id, dust);
+        assert!(get_consolidations(&account, &options, "atoi", 0).unwrap().is_empty());
+    }
+}
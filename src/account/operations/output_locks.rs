@@ -0,0 +1,181 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::OUTPUT_LOCK_TTL,
+    types::{InclusionState, OutputLock},
+    Account,
+};
+
+use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lock outputs so they don't get used by another transaction and add them to the lock journal
+pub(crate) fn lock_outputs(account: &mut Account, output_ids: &[OutputId]) {
+    let locked_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis();
+    for output_id in output_ids {
+        account.locked_outputs.insert(*output_id);
+        account.output_locks.insert(
+            *output_id,
+            OutputLock {
+                transaction_id: None,
+                locked_at,
+                ttl: OUTPUT_LOCK_TTL,
+            },
+        );
+    }
+}
+
+/// Set the transaction which uses the locked outputs
+pub(crate) fn set_lock_transaction(account: &mut Account, output_ids: &[OutputId], transaction_id: TransactionId) {
+    for output_id in output_ids {
+        if let Some(output_lock) = account.output_locks.get_mut(output_id) {
+            output_lock.transaction_id.replace(transaction_id);
+        }
+    }
+}
+
+/// Unlock outputs so they are available for new transactions again
+pub(crate) fn unlock_outputs(account: &mut Account, output_ids: &[OutputId]) {
+    for output_id in output_ids {
+        account.locked_outputs.remove(output_id);
+        account.output_locks.remove(output_id);
+    }
+}
+
+/// Store the lock journal of an account, this is done right after locks changed, so they survive a crash before the
+/// account is stored
+pub(crate) async fn save_output_locks(account: &Account) -> crate::Result<()> {
+    #[cfg(feature = "storage")]
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_output_locks(account.index, &account.output_locks)
+        .await?;
+    Ok(())
+}
+
+/// Load the lock journal of an account after it got loaded from the storage. Outputs can be locked in the journal
+/// even if the account wasn't stored anymore after they got locked. Expired locks whose transaction never got a
+/// message id are released again.
+#[cfg(feature = "storage")]
+pub(crate) async fn restore_output_locks(account: &mut Account) -> crate::Result<()> {
+    let output_locks = crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .get_output_locks(account.index)
+        .await?;
+    for output_id in output_locks.keys() {
+        account.locked_outputs.insert(*output_id);
+    }
+    account.output_locks = output_locks;
+
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis();
+    let released_output_ids = release_expired_locks(account, time_now);
+    if !released_output_ids.is_empty() {
+        log::debug!("[OUTPUT LOCKS] released expired locks {:?}", released_output_ids);
+        save_output_locks(account).await?;
+        crate::storage::manager::get()
+            .await?
+            .lock()
+            .await
+            .save_account(account)
+            .await?;
+    }
+    Ok(())
+}
+
+// Release expired locks if no pending transaction that uses the output got a message id, returns the released output
+// ids. Pending transactions without message id that use released outputs were never sent, they are set to
+// conflicting, otherwise syncing would still send them.
+fn release_expired_locks(account: &mut Account, time_now: u128) -> Vec<OutputId> {
+    let mut released_output_ids = Vec::new();
+    let mut unsent_transaction_ids: Vec<TransactionId> = Vec::new();
+    for (output_id, output_lock) in account.output_locks.iter() {
+        if output_lock.locked_at + output_lock.ttl >= time_now {
+            continue;
+        }
+        let mut sent = false;
+        let mut unsent_transactions = Vec::new();
+        for transaction_id in account.pending_transactions.iter() {
+            if let Some(transaction) = account.transactions.get(transaction_id) {
+                if transaction.input_ids().contains(output_id) {
+                    match transaction.message_id {
+                        Some(_) => sent = true,
+                        None => unsent_transactions.push(*transaction_id),
+                    }
+                }
+            }
+        }
+        if !sent {
+            released_output_ids.push(*output_id);
+            unsent_transaction_ids.extend(unsent_transactions);
+        }
+    }
+    unlock_outputs(account, &released_output_ids);
+    for transaction_id in unsent_transaction_ids {
+        account.pending_transactions.remove(&transaction_id);
+        if let Some(transaction) = account.transactions.get_mut(&transaction_id) {
+            transaction.inclusion_state = InclusionState::Conflicting;
+        }
+    }
+    released_output_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_outputs, release_expired_locks, set_lock_transaction};
+    use crate::account::{
+        test_utils::{account, address, output_id, transaction},
+        types::InclusionState,
+    };
+
+    use iota_client::bee_message::MessageId;
+
+    #[test]
+    fn expired_lock_release() {
+        let mut account = account();
+        let unsent_input = output_id(1, 0);
+        let sent_input = output_id(1, 1);
+        let unexpired_input = output_id(1, 2);
+        lock_outputs(&mut account, &[unsent_input, sent_input, unexpired_input]);
+        let unsent_transaction = transaction(&[unsent_input], &[(address(1), 1_000_000)]);
+        let mut sent_transaction = transaction(&[sent_input], &[(address(1), 2_000_000)]);
+        sent_transaction.message_id.replace(MessageId::new([1; 32]));
+        let unsent_transaction_id = unsent_transaction.payload.id();
+        let sent_transaction_id = sent_transaction.payload.id();
+        for transaction in [unsent_transaction, sent_transaction] {
+            let transaction_id = transaction.payload.id();
+            set_lock_transaction(&mut account, &transaction.input_ids(), transaction_id);
+            account.pending_transactions.insert(transaction_id);
+            account.transactions.insert(transaction_id, transaction);
+        }
+        let output_lock = &account.output_locks[&unsent_input];
+        let time_now = output_lock.locked_at + output_lock.ttl + 1;
+        account.output_locks.get_mut(&unexpired_input).unwrap().locked_at = time_now;
+
+        // only the expired lock whose transaction never got a message id is released
+        assert_eq!(release_expired_locks(&mut account, time_now), vec![unsent_input]);
+        assert!(!account.locked_outputs.contains(&unsent_input));
+        assert!(!account.output_locks.contains_key(&unsent_input));
+        assert!(account.locked_outputs.contains(&sent_input));
+        assert!(account.locked_outputs.contains(&unexpired_input));
+        assert!(account.output_locks.contains_key(&unexpired_input));
+        // the unsent transaction can't be sent anymore
+        assert!(!account.pending_transactions.contains(&unsent_transaction_id));
+        assert_eq!(
+            account.transactions[&unsent_transaction_id].inclusion_state,
+            InclusionState::Conflicting
+        );
+        assert!(account.pending_transactions.contains(&sent_transaction_id));
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::DEFAULT_REATTACH_DELAY, handle::AccountHandle, operations::transfer::submit_transaction::submit_payload,
+    types::Transaction,
+};
+
+use iota_client::bee_message::{
+    payload::{transaction::TransactionId, Payload},
+    MessageId,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time::sleep};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Defines when pending transactions get reattached, during syncing and in the background after they got submitted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReattachPolicy {
+    /// Time to wait before a transaction gets reattached the first time
+    pub delay: Duration,
+    /// Maximum amount of reattachments, `None` reattaches the transaction until it got confirmed
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: Option<usize>,
+    /// Factor by which the delay gets multiplied after each reattachment, values below 1.0 are treated as 1.0
+    pub backoff: f64,
+    /// Send transactions which are conflicting because of their inputs again, with newly selected inputs
+    #[serde(rename = "resendConflicting", default)]
+    pub resend_conflicting: bool,
+}
+
+impl Default for ReattachPolicy {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_REATTACH_DELAY,
+            max_attempts: None,
+            backoff: 1.0,
+            resend_conflicting: false,
+        }
+    }
+}
+
+impl ReattachPolicy {
+    /// The time to wait before the next reattachment, if the transaction got already reattached `attempt` times
+    pub(crate) fn delay_for_attempt(&self, attempt: usize) -> Duration {
+        let delay = self.delay.as_millis() as f64 * self.backoff.max(1.0).powi(attempt as i32);
+        // `as` saturates, so the delay can't overflow
+        Duration::from_millis(delay as u64)
+    }
+
+    /// Check if a transaction can still be reattached
+    pub(crate) fn allows_attempt(&self, attempt: usize) -> bool {
+        match self.max_attempts {
+            Some(max_attempts) => attempt < max_attempts,
+            None => true,
+        }
+    }
+
+    /// Check if the delay since the latest attachment of a transaction passed and it should be reattached
+    pub(crate) fn should_reattach(&self, transaction: &Transaction, time_now: u128) -> bool {
+        let last_attachment = transaction.last_reattachment.unwrap_or(transaction.timestamp);
+        self.allows_attempt(transaction.reattachments)
+            && last_attachment + self.delay_for_attempt(transaction.reattachments).as_millis() < time_now
+    }
+}
+
+/// Background tasks which try to get submitted transactions confirmed. They belong to an account and get aborted when
+/// the last handle to it is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct RetryTasks(Mutex<HashMap<TransactionId, JoinHandle<()>>>);
+
+impl RetryTasks {
+    /// Spawn a task for a transaction, an older task for the same transaction gets aborted. The task is spawned while
+    /// the tasks are locked, so it can't remove itself before it got added.
+    pub(crate) fn spawn<F>(&self, transaction_id: TransactionId, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if let Ok(mut tasks) = self.0.lock() {
+            if let Some(old_task) = tasks.insert(transaction_id, tokio::spawn(task)) {
+                old_task.abort();
+            }
+        }
+    }
+
+    /// Remove the task of a transaction without aborting it, used by tasks which finished
+    pub(crate) fn remove(&self, transaction_id: &TransactionId) {
+        if let Ok(mut tasks) = self.0.lock() {
+            tasks.remove(transaction_id);
+        }
+    }
+
+    /// Check if a task for a transaction is running
+    pub(crate) fn contains(&self, transaction_id: &TransactionId) -> bool {
+        self.0.lock().map_or(false, |tasks| tasks.contains_key(transaction_id))
+    }
+
+    /// Abort the task for a transaction, used when it got confirmed or is conflicting
+    pub(crate) fn abort(&self, transaction_id: &TransactionId) {
+        if let Ok(mut tasks) = self.0.lock() {
+            if let Some(task) = tasks.remove(transaction_id) {
+                task.abort();
+            }
+        }
+    }
+}
+
+impl Drop for RetryTasks {
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.0.get_mut() {
+            for (_, task) in tasks.drain() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Spawn a task which reattaches or promotes a submitted transaction until it got referenced by a milestone, isn't
+/// pending anymore or the attempts of the reattach policy are used up. The task only holds a weak handle, so it doesn't
+/// keep the account alive.
+pub(crate) fn start_retry_task(account_handle: &AccountHandle, transaction_id: TransactionId, policy: ReattachPolicy) {
+    let weak_handle = account_handle.downgrade();
+    account_handle.retry_tasks.spawn(transaction_id, async move {
+        let mut retries = 0;
+        loop {
+            let attempt = match weak_handle.upgrade() {
+                Some(account_handle) => match get_pending_transaction(&account_handle, &transaction_id).await {
+                    Ok(transaction) => retry_attempt(&transaction, retries),
+                    Err(_) => break,
+                },
+                None => return,
+            };
+            if !policy.allows_attempt(attempt) {
+                break;
+            }
+            sleep(policy.delay_for_attempt(attempt)).await;
+            let account_handle = match weak_handle.upgrade() {
+                Some(account_handle) => account_handle,
+                None => return,
+            };
+            retries = attempt + 1;
+            match retry_message(&account_handle, transaction_id).await {
+                // syncing updates the inclusion state
+                Ok((_, true)) => break,
+                Ok((message_id, false)) => log::debug!("[RETRY] retried {} with {}", transaction_id, message_id),
+                Err(crate::Error::TransactionNotPending(_)) | Err(crate::Error::TransactionNotFound(_)) => break,
+                Err(e) => log::debug!("[RETRY] retrying {} failed: {}", transaction_id, e),
+            }
+        }
+        if let Some(account_handle) = weak_handle.upgrade() {
+            account_handle.retry_tasks.remove(&transaction_id);
+        }
+    });
+}
+
+// The attempt of a retry task which retried the transaction `retries` times. Reattachments during syncing and by earlier
+// tasks are stored with the transaction and count as well, so a new task doesn't get new attempts.
+fn retry_attempt(transaction: &Transaction, retries: usize) -> usize {
+    transaction.reattachments.max(retries)
+}
+
+/// Reattach or promote the message of a pending transaction, depending on what the node suggests. Returns the id of
+/// the new message, or the current message id if the message doesn't need to be reattached or promoted.
+pub(crate) async fn retry(account_handle: &AccountHandle, transaction_id: TransactionId) -> crate::Result<MessageId> {
+    Ok(retry_message(account_handle, transaction_id).await?.0)
+}
+
+// Like `retry`, also returns true if the message got referenced by a milestone already
+async fn retry_message(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+) -> crate::Result<(MessageId, bool)> {
+    log::debug!("[RETRY] retry {}", transaction_id);
+    let message_id = match get_pending_transaction(account_handle, &transaction_id)
+        .await?
+        .message_id
+    {
+        Some(message_id) => message_id,
+        // the transaction wasn't submitted yet
+        None => return Ok((reattach(account_handle, transaction_id).await?, false)),
+    };
+    let client = crate::client::get_client().await?;
+    let metadata = client.get_message().metadata(&message_id).await?;
+    if metadata.ledger_inclusion_state.is_some() {
+        log::debug!("[RETRY] {} is already referenced", message_id);
+        Ok((message_id, true))
+    } else if metadata.should_reattach.unwrap_or(false) {
+        Ok((reattach(account_handle, transaction_id).await?, false))
+    } else if metadata.should_promote.unwrap_or(false) {
+        Ok((promote(account_handle, transaction_id).await?, false))
+    } else {
+        log::debug!("[RETRY] {} doesn't need to be reattached or promoted", message_id);
+        Ok((message_id, false))
+    }
+}
+
+/// Reattach the transaction payload in a new message, returns the id of the new message
+pub(crate) async fn reattach(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+) -> crate::Result<MessageId> {
+    log::debug!("[RETRY] reattach {}", transaction_id);
+    let transaction = get_pending_transaction(account_handle, &transaction_id).await?;
+    // submitted without a new retry task, this is also called from the retry task
+    let message_id = submit_payload(account_handle, Payload::Transaction(Box::new(transaction.payload))).await?;
+
+    let mut account = account_handle.write().await;
+    if let Some(transaction) = account.transactions.get_mut(&transaction_id) {
+        // the first submission doesn't count as reattachment
+        if transaction.message_id.is_some() {
+            transaction.reattachments += 1;
+            transaction.last_reattachment.replace(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_millis(),
+            );
+        }
+        transaction.message_id.replace(message_id);
+    }
+    #[cfg(feature = "storage")]
+    log::debug!("[RETRY] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(message_id)
+}
+
+/// Promote the message of a transaction, returns the id of the promotion message
+pub(crate) async fn promote(account_handle: &AccountHandle, transaction_id: TransactionId) -> crate::Result<MessageId> {
+    log::debug!("[RETRY] promote {}", transaction_id);
+    let message_id = get_pending_transaction(account_handle, &transaction_id)
+        .await?
+        .message_id
+        .ok_or(crate::Error::MessageNotFound)?;
+    let client = crate::client::get_client().await?;
+    let (promotion_message_id, _) = client.promote(&message_id).await?;
+    Ok(promotion_message_id)
+}
+
+// Get a transaction if it's an outgoing pending transaction which wasn't cancelled or replaced
+async fn get_pending_transaction(
+    account_handle: &AccountHandle,
+    transaction_id: &TransactionId,
+) -> crate::Result<Transaction> {
+    let account = account_handle.read().await;
+    let transaction = account
+        .transactions
+        .get(transaction_id)
+        .ok_or(crate::Error::TransactionNotFound(*transaction_id))?;
+    if transaction.incoming
+        || transaction.replaced_by.is_some()
+        || !account.pending_transactions.contains(transaction_id)
+    {
+        return Err(crate::Error::TransactionNotPending(*transaction_id));
+    }
+    Ok(transaction.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_attempt, ReattachPolicy};
+    use crate::account::test_utils::{address, output_id, transaction};
+
+    use std::time::Duration;
+
+    #[test]
+    fn reattach_policy_delay() {
+        let policy = ReattachPolicy {
+            delay: Duration::from_secs(10),
+            max_attempts: Some(3),
+            backoff: 2.0,
+            resend_conflicting: false,
+        };
+        assert_eq!(policy.delay_for_attempt(0), Duration::from_secs(10));
+        assert_eq!(policy.delay_for_attempt(2), Duration::from_secs(40));
+        assert!(policy.allows_attempt(2));
+        assert!(!policy.allows_attempt(3));
+
+        // a backoff below 1 doesn't decrease the delay
+        let policy = ReattachPolicy {
+            backoff: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay_for_attempt(5), policy.delay);
+        assert!(policy.allows_attempt(usize::MAX - 1));
+    }
+
+    #[test]
+    fn retry_attempts() {
+        let policy = ReattachPolicy {
+            max_attempts: Some(3),
+            ..Default::default()
+        };
+        let mut transaction = transaction(&[output_id(1, 0)], &[(address(1), 1_000_000)]);
+        assert!(policy.allows_attempt(retry_attempt(&transaction, 0)));
+        // reattachments during syncing or by an earlier task use up the attempts of a new task
+        transaction.reattachments = 3;
+        assert!(!policy.allows_attempt(retry_attempt(&transaction, 0)));
+        // retries of the task count if they didn't reattach the transaction
+        transaction.reattachments = 1;
+        assert!(policy.allows_attempt(retry_attempt(&transaction, 2)));
+        assert!(!policy.allows_attempt(retry_attempt(&transaction, 3)));
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    handle::AccountHandle,
+    operations::transfer::TransferOutput,
+    types::{address::parse_bech32_address, InclusionState},
+};
+#[cfg(feature = "events")]
+use crate::events::types::{SpendingPolicyViolationEvent, WalletEvent};
+
+use iota_client::bee_message::{
+    output::Output,
+    payload::transaction::{Essence, TransactionId},
+};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DAY_IN_MILLIS: u128 = 24 * 60 * 60 * 1000;
+const WEEK_IN_MILLIS: u128 = 7 * DAY_IN_MILLIS;
+
+/// Limits for the outgoing transfers of an account, they're checked before a transaction gets signed. Outputs to
+/// addresses of the account itself aren't counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendingPolicy {
+    /// Maximum amount of a single transaction
+    #[serde(rename = "maxPerTransaction", default)]
+    pub max_per_transaction: Option<u64>,
+    /// Maximum amount that can be sent in the last 24 hours
+    #[serde(rename = "dailyLimit", default)]
+    pub daily_limit: Option<u64>,
+    /// Maximum amount that can be sent in the last 7 days
+    #[serde(rename = "weeklyLimit", default)]
+    pub weekly_limit: Option<u64>,
+    /// If set, only these bech32 addresses can receive funds
+    #[serde(default)]
+    pub allowlist: Option<Vec<String>>,
+    /// Bech32 addresses that can't receive funds
+    #[serde(default)]
+    pub blocklist: Vec<String>,
+    /// Outputs with a higher amount need to be approved by the handler set with
+    /// [`crate::account::handle::AccountHandle::set_spending_approval_handler()`]
+    #[serde(rename = "approvalThreshold", default)]
+    pub approval_threshold: Option<u64>,
+}
+
+impl SpendingPolicy {
+    // Checks the outputs to other addresses against the policy, the amounts sent in the last day and week are required
+    // for the rolling limits. Returns the outputs that need approval.
+    fn check<'a>(
+        &self,
+        outputs: &[&'a TransferOutput],
+        sent_last_day: u64,
+        sent_last_week: u64,
+    ) -> std::result::Result<Vec<&'a TransferOutput>, String> {
+        for output in outputs {
+            if self.blocklist.contains(&output.address) {
+                return Err(format!("{} is on the blocklist", output.address));
+            }
+            if let Some(allowlist) = &self.allowlist {
+                if !allowlist.contains(&output.address) {
+                    return Err(format!("{} isn't on the allowlist", output.address));
+                }
+            }
+        }
+        let amount: u64 = outputs.iter().map(|o| o.amount).sum();
+        if let Some(max_per_transaction) = self.max_per_transaction {
+            if amount > max_per_transaction {
+                return Err(format!(
+                    "amount {}i exceeds the maximum of {}i per transaction",
+                    amount, max_per_transaction
+                ));
+            }
+        }
+        if let Some(daily_limit) = self.daily_limit {
+            if sent_last_day.saturating_add(amount) > daily_limit {
+                return Err(format!(
+                    "amount {}i exceeds the daily limit of {}i, {}i were sent in the last 24 hours",
+                    amount, daily_limit, sent_last_day
+                ));
+            }
+        }
+        if let Some(weekly_limit) = self.weekly_limit {
+            if sent_last_week.saturating_add(amount) > weekly_limit {
+                return Err(format!(
+                    "amount {}i exceeds the weekly limit of {}i, {}i were sent in the last 7 days",
+                    amount, weekly_limit, sent_last_week
+                ));
+            }
+        }
+        Ok(match self.approval_threshold {
+            Some(threshold) => outputs.iter().filter(|o| o.amount > threshold).copied().collect(),
+            None => Vec::new(),
+        })
+    }
+}
+
+/// Handler which approves outputs above the approval threshold of the [`SpendingPolicy`], returns true to approve them
+#[derive(Clone)]
+pub(crate) struct SpendingApprovalHandler(pub(crate) Arc<dyn Fn(&[TransferOutput]) -> bool + Send + Sync>);
+
+impl Debug for SpendingApprovalHandler {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "SpendingApprovalHandler")
+    }
+}
+
+/// Amounts of transfers which passed the spending policy, but whose transaction isn't stored in the account yet. They
+/// count towards the rolling limits, so transfers running at the same time can't exceed the limits together.
+#[derive(Debug, Default)]
+pub(crate) struct ReservedSpending {
+    next_id: AtomicU64,
+    amounts: Mutex<HashMap<u64, u64>>,
+}
+
+impl ReservedSpending {
+    // the amounts of a poisoned lock are still used, without them running transfers could exceed the limits together
+    fn amounts(&self) -> MutexGuard<'_, HashMap<u64, u64>> {
+        self.amounts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn reserve(self: &Arc<Self>, amount: u64) -> SpendingReservation {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.amounts().insert(id, amount);
+        SpendingReservation(Some((self.clone(), id)))
+    }
+}
+
+/// An amount reserved for the rolling limits of the spending policy, it's released when dropped. It needs to be kept
+/// until the transaction is stored in the account.
+pub(crate) struct SpendingReservation(Option<(Arc<ReservedSpending>, u64)>);
+
+impl Drop for SpendingReservation {
+    fn drop(&mut self) {
+        if let Some((reserved_spending, id)) = &self.0 {
+            reserved_spending.amounts().remove(id);
+        }
+    }
+}
+
+/// Checks the outputs of a transfer against the spending policy of the account. A violation returns
+/// [`crate::Error::SpendingPolicyViolation`] and emits an event. The amount to other addresses is reserved for the
+/// rolling limits until the returned reservation is dropped. The transaction a replacement `replaces` isn't counted,
+/// because only one of them can get confirmed.
+pub(crate) async fn check_spending_policy(
+    account_handle: &AccountHandle,
+    outputs: &[TransferOutput],
+    replaces: Option<TransactionId>,
+) -> crate::Result<SpendingReservation> {
+    // the write lock prevents that another transfer gets checked or stored before the amount is reserved
+    let account = account_handle.write().await;
+    let policy = account.account_options.spending_policy.clone();
+    if policy == SpendingPolicy::default() {
+        return Ok(SpendingReservation(None));
+    }
+    log::debug!("[SPENDING POLICY] check outputs");
+    // outputs to the account itself don't move funds away, like consolidations or remainders
+    let external_outputs: Vec<&TransferOutput> = outputs
+        .iter()
+        .filter(|output| match parse_bech32_address(&output.address) {
+            Ok(address) => !account.is_account_address(&address.inner),
+            Err(_) => true,
+        })
+        .collect();
+
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis();
+    let mut sent_last_day = 0;
+    let mut sent_last_week = 0;
+    for (transaction_id, transaction) in account.transactions.iter() {
+        if Some(*transaction_id) == replaces
+            || transaction.incoming
+            || transaction.inclusion_state == InclusionState::Conflicting
+            || transaction.timestamp + WEEK_IN_MILLIS < time_now
+        {
+            continue;
+        }
+        let Essence::Regular(essence) = transaction.payload.essence();
+        let sent: u64 = essence
+            .outputs()
+            .iter()
+            .filter_map(|output| match output {
+                Output::SignatureLockedSingle(output) => Some((output.address(), output.amount())),
+                Output::SignatureLockedDustAllowance(output) => Some((output.address(), output.amount())),
+                _ => None,
+            })
+            .filter(|(address, _)| !account_addresses.contains(address))
+            .map(|(_, amount)| amount)
+            .sum();
+        sent_last_week += sent;
+        if transaction.timestamp + DAY_IN_MILLIS >= time_now {
+            sent_last_day += sent;
+        }
+    }
+    let reserved_amount: u64 = account_handle.reserved_spending.amounts().values().sum();
+    let check_result = policy.check(
+        &external_outputs,
+        sent_last_day.saturating_add(reserved_amount),
+        sent_last_week.saturating_add(reserved_amount),
+    );
+    let reservation = match check_result {
+        Ok(_) => account_handle
+            .reserved_spending
+            .reserve(external_outputs.iter().map(|o| o.amount).sum()),
+        Err(_) => SpendingReservation(None),
+    };
+    let account_index = account.index;
+    drop(account);
+
+    // a rejected approval drops the reservation again
+    let result = match check_result {
+        Ok(outputs_to_approve) if outputs_to_approve.is_empty() => Ok(()),
+        Ok(outputs_to_approve) => {
+            let outputs_to_approve: Vec<TransferOutput> = outputs_to_approve.into_iter().cloned().collect();
+            match &*account_handle.spending_approval_handler.lock().await {
+                Some(handler) if (handler.0)(&outputs_to_approve) => Ok(()),
+                Some(_) => Err("outputs above the approval threshold were rejected".to_string()),
+                None => Err("outputs above the approval threshold, but no approval handler is set".to_string()),
+            }
+        }
+        Err(reason) => Err(reason),
+    };
+    if let Err(reason) = result {
+        log::debug!("[SPENDING POLICY] violation: {}", reason);
+        #[cfg(feature = "events")]
+        account_handle.event_emitter.lock().await.emit(
+            account_index,
+            WalletEvent::SpendingPolicyViolation(SpendingPolicyViolationEvent { reason: reason.clone() }),
+        );
+        return Err(crate::Error::SpendingPolicyViolation(reason));
+    }
+    Ok(reservation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpendingPolicy;
+    use crate::account::TransferOutput;
+
+    #[test]
+    fn spending_policy_limits() {
+        let output = |address: &str, amount| TransferOutput {
+            address: address.to_string(),
+            amount,
+            output_kind: None,
+        };
+        let policy = SpendingPolicy {
+            max_per_transaction: Some(10_000_000),
+            daily_limit: Some(15_000_000),
+            blocklist: vec!["blocked".to_string()],
+            approval_threshold: Some(5_000_000),
+            ..Default::default()
+        };
+        let small = output("recipient", 1_000_000);
+        let big = output("recipient", 6_000_000);
+        assert!(policy.check(&[&small], 0, 0).unwrap().is_empty());
+        assert_eq!(policy.check(&[&small, &big], 0, 0).unwrap().len(), 1);
+        // the rolling daily limit includes the amount sent before
+        assert!(policy.check(&[&big], 10_000_000, 10_000_000).is_err());
+        assert!(policy.check(&[&big, &big], 0, 0).is_err());
+        assert!(policy.check(&[&output("blocked", 1)], 0, 0).is_err());
+    }
+}
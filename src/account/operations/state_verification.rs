@@ -0,0 +1,422 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::PARALLEL_REQUESTS_AMOUNT,
+    handle::AccountHandle,
+    operations::{
+        output_locks::{save_output_locks, unlock_outputs},
+        syncing::{
+            requests::{is_not_found, request_with_retries},
+            sync_account, SyncOptions,
+        },
+    },
+    types::InclusionState,
+    Account,
+};
+#[cfg(feature = "events")]
+use crate::events::types::{TransactionInclusionEvent, WalletEvent};
+
+use iota_client::bee_message::{input::UtxoInput, output::OutputId, payload::transaction::TransactionId};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Differences between the local state of an account and the ledger of the node, returned from
+/// [`crate::account::handle::AccountHandle::verify_state()`] and [`crate::account::handle::AccountHandle::repair()`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateVerificationReport {
+    /// Local unspent outputs which don't exist on the node
+    #[serde(rename = "phantomOutputs")]
+    pub phantom_outputs: Vec<OutputId>,
+    /// Local unspent outputs which are spent on the node
+    #[serde(rename = "missedSpends")]
+    pub missed_spends: Vec<OutputId>,
+    /// Locked outputs which aren't used by a pending transaction and whose lock expired
+    #[serde(rename = "staleLocks")]
+    pub stale_locks: Vec<OutputId>,
+    /// Pending transactions which can't get confirmed anymore or are already confirmed
+    #[serde(rename = "orphanedPendingTransactions")]
+    pub orphaned_pending_transactions: Vec<OrphanedTransaction>,
+    /// Outputs whose state couldn't be requested, nothing is repaired for them
+    #[serde(rename = "uncheckedOutputs")]
+    pub unchecked_outputs: Vec<OutputId>,
+}
+
+impl StateVerificationReport {
+    /// Returns true if no discrepancies were found
+    pub fn is_consistent(&self) -> bool {
+        self.phantom_outputs.is_empty()
+            && self.missed_spends.is_empty()
+            && self.stale_locks.is_empty()
+            && self.orphaned_pending_transactions.is_empty()
+    }
+}
+
+/// A pending transaction which isn't pending on the node anymore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanedTransaction {
+    #[serde(rename = "transactionId")]
+    pub transaction_id: TransactionId,
+    /// The transaction got confirmed, otherwise an input was spent by another transaction or doesn't exist
+    pub confirmed: bool,
+}
+
+// The state of an output on the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputState {
+    Unspent,
+    Spent,
+    NotFound,
+    Failed,
+}
+
+/// Compare the unspent outputs, locked outputs and pending transactions of the account with the node
+pub(crate) async fn verify_state(account_handle: &AccountHandle) -> crate::Result<StateVerificationReport> {
+    log::debug!("[VERIFY] verify state");
+    let client = crate::client::get_client().await?;
+    let network_id = client.get_network_id().await?;
+    let account = account_handle.read().await;
+
+    // outputs of other networks can't be checked
+    let unspent_output_ids: Vec<OutputId> = account
+        .unspent_outputs
+        .values()
+        .filter(|output| output.network_id == network_id)
+        .map(|output| output.output_id)
+        .collect();
+    let mut pending_transactions = Vec::new();
+    for transaction_id in account.pending_transactions.iter() {
+        if let Some(transaction) = account.transactions.get(transaction_id) {
+            if transaction.network_id == network_id {
+                pending_transactions.push((*transaction_id, get_input_ids(&account, transaction_id)));
+            }
+        }
+    }
+    let mut output_ids: HashSet<OutputId> = unspent_output_ids.iter().copied().collect();
+    for (transaction_id, input_ids) in pending_transactions.iter() {
+        output_ids.extend(input_ids.iter().copied());
+        // the first output of a transaction exists once it's confirmed
+        output_ids.insert(OutputId::new(*transaction_id, 0)?);
+    }
+    let stale_locks = get_stale_locks(&account);
+    drop(account);
+
+    let output_states = get_output_states(output_ids.into_iter().collect()).await?;
+    let report = create_report(unspent_output_ids, pending_transactions, stale_locks, &output_states)?;
+    log::debug!("[VERIFY] {:?}", report);
+    Ok(report)
+}
+
+// Compare the local state with the states of the outputs on the node
+fn create_report(
+    unspent_output_ids: Vec<OutputId>,
+    pending_transactions: Vec<(TransactionId, Vec<OutputId>)>,
+    stale_locks: Vec<OutputId>,
+    output_states: &HashMap<OutputId, OutputState>,
+) -> crate::Result<StateVerificationReport> {
+    let mut report = StateVerificationReport {
+        stale_locks,
+        ..Default::default()
+    };
+    for output_id in unspent_output_ids {
+        match output_states.get(&output_id) {
+            Some(OutputState::NotFound) => report.phantom_outputs.push(output_id),
+            Some(OutputState::Spent) => report.missed_spends.push(output_id),
+            Some(OutputState::Failed) => report.unchecked_outputs.push(output_id),
+            _ => {}
+        }
+    }
+    for (transaction_id, input_ids) in pending_transactions {
+        let first_output_state = output_states.get(&OutputId::new(transaction_id, 0)?);
+        if first_output_state == Some(&OutputState::Failed) {
+            continue;
+        }
+        let confirmed = matches!(first_output_state, Some(OutputState::Unspent | OutputState::Spent));
+        let inputs_unavailable = input_ids.iter().any(|input_id| {
+            matches!(
+                output_states.get(input_id),
+                Some(OutputState::Spent | OutputState::NotFound)
+            )
+        });
+        if confirmed || inputs_unavailable {
+            report.orphaned_pending_transactions.push(OrphanedTransaction {
+                transaction_id,
+                confirmed,
+            });
+        }
+    }
+    Ok(report)
+}
+
+// Get the locked outputs which aren't used by a pending transaction and whose lock expired
+fn get_stale_locks(account: &Account) -> Vec<OutputId> {
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis();
+    // also pending transactions of other networks use their locked outputs
+    let used_by_pending_transactions: HashSet<OutputId> = account
+        .pending_transactions
+        .iter()
+        .flat_map(|transaction_id| get_input_ids(account, transaction_id))
+        .collect();
+    account
+        .locked_outputs
+        .iter()
+        .filter(|output_id| {
+            !used_by_pending_transactions.contains(output_id)
+                && account
+                    .output_locks
+                    .get(output_id)
+                    .map_or(true, |output_lock| output_lock.locked_at + output_lock.ttl < time_now)
+        })
+        .copied()
+        .collect()
+}
+
+/// Verify the state and fix the discrepancies, returns the repaired discrepancies. The account is stored afterwards and
+/// synced if a pending transaction was confirmed, so its outputs are added.
+pub(crate) async fn repair(account_handle: &AccountHandle) -> crate::Result<StateVerificationReport> {
+    // prevent syncing while the state gets repaired
+    let last_synced = account_handle.last_synced.lock().await;
+    let mut report = verify_state(account_handle).await?;
+    if report.is_consistent() {
+        return Ok(report);
+    }
+    log::debug!("[VERIFY] repair state");
+    let mut account = account_handle.write().await;
+    // locks could have been set or released while the node was requested
+    report.stale_locks = get_stale_locks(&account);
+
+    for output_id in report.phantom_outputs.iter() {
+        account.outputs.remove(output_id);
+        remove_unspent_output(&mut account, output_id);
+    }
+    for output_id in report.missed_spends.iter() {
+        if let Some(output) = account.outputs.get_mut(output_id) {
+            output.is_spent = true;
+        }
+        remove_unspent_output(&mut account, output_id);
+    }
+    unlock_outputs(&mut account, &report.stale_locks);
+
+    for orphaned_transaction in report.orphaned_pending_transactions.iter() {
+        let transaction_id = orphaned_transaction.transaction_id;
+        if !account.pending_transactions.remove(&transaction_id) {
+            continue;
+        }
+        account_handle.retry_tasks.abort(&transaction_id);
+        let inclusion_state = if orphaned_transaction.confirmed {
+            InclusionState::Confirmed
+        } else {
+            InclusionState::Conflicting
+        };
+        for input_id in get_input_ids(&account, &transaction_id) {
+            // the inputs of a confirmed transaction are spent, the ones of a conflicting transaction only if another
+            // transaction spent them
+            if orphaned_transaction.confirmed {
+                if let Some(output) = account.outputs.get_mut(&input_id) {
+                    output.is_spent = true;
+                }
+                remove_unspent_output(&mut account, &input_id);
+            } else {
+                unlock_outputs(&mut account, &[input_id]);
+            }
+        }
+        if let Some(transaction) = account.transactions.get_mut(&transaction_id) {
+            transaction.inclusion_state = inclusion_state.clone();
+        }
+        #[cfg(feature = "events")]
+        account_handle.event_emitter.lock().await.emit(
+            account.index,
+            WalletEvent::TransactionInclusion(TransactionInclusionEvent {
+                transaction_id,
+                inclusion_state,
+                conflict_reason: None,
+            }),
+        );
+    }
+
+    #[cfg(feature = "storage")]
+    log::debug!("[VERIFY] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    save_output_locks(&account).await?;
+    drop(account);
+    drop(last_synced);
+
+    // the outputs of confirmed transactions are found with the addresses of the account
+    if report
+        .orphaned_pending_transactions
+        .iter()
+        .any(|orphaned_transaction| orphaned_transaction.confirmed)
+    {
+        sync_account(
+            account_handle,
+            &SyncOptions {
+                force_syncing: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+    }
+    Ok(report)
+}
+
+// Get the input ids of a transaction of the account
+fn get_input_ids(account: &Account, transaction_id: &TransactionId) -> Vec<OutputId> {
+    account
+        .transactions
+        .get(transaction_id)
+        .map(|transaction| transaction.input_ids())
+        .unwrap_or_default()
+}
+
+// Remove an output from the unspent outputs, its locks and the balance of its address
+pub(crate) fn remove_unspent_output(account: &mut Account, output_id: &OutputId) {
+    let output = match account.unspent_outputs.remove(output_id) {
+        Some(output) => output,
+        None => return,
+    };
+    account.locked_outputs.remove(output_id);
+    account.output_locks.remove(output_id);
+    account.frozen_outputs.remove(output_id);
+    if let Some(position) = account
+        .addresses_with_balance
+        .iter()
+        .position(|a| a.address.inner == output.address)
+    {
+        let address_with_balance = &mut account.addresses_with_balance[position];
+        address_with_balance.balance = address_with_balance.balance.saturating_sub(output.amount);
+        address_with_balance.output_ids.retain(|o| o != output_id);
+        if address_with_balance.output_ids.is_empty() {
+            account.addresses_with_balance.remove(position);
+        }
+    }
+}
+
+// Request the state of outputs from the node
+async fn get_output_states(output_ids: Vec<OutputId>) -> crate::Result<HashMap<OutputId, OutputState>> {
+    let client = crate::client::get_client().await?;
+    let mut output_states = HashMap::new();
+    for output_ids_chunk in output_ids
+        .chunks(PARALLEL_REQUESTS_AMOUNT)
+        .map(|x: &[OutputId]| x.to_vec())
+    {
+        let mut tasks = Vec::new();
+        for output_id in output_ids_chunk {
+            let client = client.clone();
+            tasks.push(async move {
+                tokio::spawn(async move {
+                    let client = &client;
+                    let output =
+                        request_with_retries(
+                            || async move { Ok(client.get_output(&UtxoInput::from(output_id)).await?) },
+                        )
+                        .await;
+                    let output_state = match output {
+                        Ok(output) if output.is_spent => OutputState::Spent,
+                        Ok(_) => OutputState::Unspent,
+                        Err(e) if is_not_found(&e) => OutputState::NotFound,
+                        Err(e) => {
+                            log::debug!("[VERIFY] failed to get output {}: {}", output_id, e);
+                            OutputState::Failed
+                        }
+                    };
+                    (output_id, output_state)
+                })
+                .await
+            });
+        }
+        output_states.extend(futures::future::try_join_all(tasks).await?);
+    }
+    Ok(output_states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_report, OrphanedTransaction, OutputState, StateVerificationReport};
+    use crate::account::test_utils::output_id;
+
+    use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId};
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn state_verification_consistency() {
+        assert!(StateVerificationReport::default().is_consistent());
+        // outputs which couldn't be checked aren't a discrepancy
+        let report = StateVerificationReport {
+            unchecked_outputs: vec![output_id(1, 0)],
+            ..Default::default()
+        };
+        assert!(report.is_consistent());
+        let report = StateVerificationReport {
+            orphaned_pending_transactions: vec![OrphanedTransaction {
+                transaction_id: TransactionId::new([1; 32]),
+                confirmed: true,
+            }],
+            ..Default::default()
+        };
+        assert!(!report.is_consistent());
+    }
+
+    #[test]
+    fn state_verification_classification() {
+        let unspent_output_ids = vec![output_id(1, 0), output_id(1, 1), output_id(1, 2), output_id(1, 3)];
+        let confirmed_transaction = TransactionId::new([2; 32]);
+        let conflicting_transaction = TransactionId::new([3; 32]);
+        let pending_transaction = TransactionId::new([4; 32]);
+        let unchecked_transaction = TransactionId::new([5; 32]);
+        let output_states: HashMap<OutputId, OutputState> = vec![
+            (output_id(1, 0), OutputState::Unspent),
+            (output_id(1, 1), OutputState::NotFound),
+            (output_id(1, 2), OutputState::Spent),
+            (output_id(1, 3), OutputState::Failed),
+            (output_id(2, 0), OutputState::Spent),
+            (output_id(3, 0), OutputState::NotFound),
+            (output_id(4, 0), OutputState::NotFound),
+            (output_id(5, 0), OutputState::Failed),
+            (output_id(6, 0), OutputState::Spent),
+            (output_id(7, 0), OutputState::Unspent),
+        ]
+        .into_iter()
+        .collect();
+        let pending_transactions = vec![
+            (confirmed_transaction, vec![output_id(7, 0)]),
+            (conflicting_transaction, vec![output_id(6, 0)]),
+            (pending_transaction, vec![output_id(7, 0)]),
+            (unchecked_transaction, vec![output_id(6, 0)]),
+        ];
+
+        let report = create_report(
+            unspent_output_ids,
+            pending_transactions,
+            vec![output_id(8, 0)],
+            &output_states,
+        )
+        .unwrap();
+        assert_eq!(report.phantom_outputs, vec![output_id(1, 1)]);
+        assert_eq!(report.missed_spends, vec![output_id(1, 2)]);
+        assert_eq!(report.unchecked_outputs, vec![output_id(1, 3)]);
+        assert_eq!(report.stale_locks, vec![output_id(8, 0)]);
+        let orphaned: Vec<(TransactionId, bool)> = report
+            .orphaned_pending_transactions
+            .iter()
+            .map(|orphaned_transaction| (orphaned_transaction.transaction_id, orphaned_transaction.confirmed))
+            .collect();
+        // the transaction whose first output can't be checked stays pending
+        assert_eq!(
+            orphaned,
+            vec![(confirmed_transaction, true), (conflicting_transaction, false)]
+        );
+    }
+}
@@ -1,28 +1,171 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::account::{
-    constants::PARALLEL_REQUESTS_AMOUNT,
-    handle::AccountHandle,
-    operations::syncing::SyncOptions,
-    types::address::{AccountAddress, AddressWithBalance},
-};
 #[cfg(feature = "events")]
 use crate::events::types::WalletEvent;
+use crate::{
+    account::{
+        constants::PARALLEL_REQUESTS_AMOUNT,
+        handle::AccountHandle,
+        operations::{
+            address_generation::{generate_account_addresses, AddressGenerationOptions},
+            syncing::{
+                check_cancellation, progress::SyncProgress, requests::request_with_retries, SyncOptions, SyncPhase,
+                SyncReport,
+            },
+        },
+        types::address::{AccountAddress, AddressWithBalance},
+    },
+    signing::GenerateAddressMetadata,
+};
+
+use iota_client::{
+    bee_message::output::OutputId, bee_rest_api::types::responses::OutputsAddressResponse, node::OutputsOptions,
+};
 
-use iota_client::{bee_message::output::OutputId, bee_rest_api::types::responses::OutputsAddressResponse};
+use std::{collections::HashSet, str::FromStr, time::Instant};
 
-use std::{str::FromStr, time::Instant};
+/// Generate public and internal addresses after the highest known index, until `gap_limit` addresses in a row are
+/// unused. This finds funds on addresses which were generated by another instance with the same seed. The addresses up
+/// to the last used one are returned, they aren't stored in the account before the syncing finishes. Addresses whose
+/// outputs couldn't be requested are kept and reported as failed.
+pub(crate) async fn discover_addresses(
+    account_handle: &AccountHandle,
+    options: &SyncOptions,
+    report: &mut SyncReport,
+) -> crate::Result<Vec<AccountAddress>> {
+    log::debug!("[SYNC] discover addresses with gap limit {}", options.gap_limit);
+    let client = crate::client::get_client().await?;
+    let network = match client.get_bech32_hrp().await?.as_str() {
+        "iota" => crate::signing::Network::Mainnet,
+        _ => crate::signing::Network::Testnet,
+    };
+
+    let mut discovered_addresses = Vec::new();
+    for internal in [false, true] {
+        let address_generation_options = AddressGenerationOptions {
+            internal,
+            metadata: GenerateAddressMetadata {
+                network: network.clone(),
+                syncing: true,
+            },
+        };
+        let mut generated_addresses = Vec::new();
+        let mut used_indexes = HashSet::new();
+        let mut failed_indexes = HashSet::new();
+        loop {
+            check_cancellation(options)?;
+            let addresses = {
+                let account = account_handle.read().await;
+                let start_index = if internal {
+                    account.internal_addresses.len()
+                } else {
+                    account.public_addresses.len()
+                } + generated_addresses.len();
+                generate_account_addresses(
+                    account_handle,
+                    &account,
+                    start_index,
+                    options.gap_limit,
+                    &address_generation_options,
+                )
+                .await?
+            };
+            generated_addresses.extend(addresses.iter().cloned());
+            report.output_ids_requests += addresses.len();
+            let mut tasks = Vec::new();
+            for address in addresses {
+                let client = client.clone();
+                tasks.push(async move {
+                    tokio::spawn(async move {
+                        let client = &client;
+                        let bech32_address = &address.address().to_bech32();
+                        // spent outputs are included, an address which received funds before is used also without
+                        // balance
+                        let outputs_response = request_with_retries(|| async move {
+                            Ok(client
+                                .get_address()
+                                .outputs_response(
+                                    bech32_address,
+                                    OutputsOptions {
+                                        include_spent: true,
+                                        output_type: None,
+                                    },
+                                )
+                                .await?)
+                        })
+                        .await;
+                        (address, outputs_response)
+                    })
+                    .await
+                });
+            }
+            let mut highest_used_index = None;
+            for (address, outputs_response) in futures::future::try_join_all(tasks).await? {
+                match outputs_response {
+                    Ok(outputs_response) if !outputs_response.output_ids.is_empty() => {
+                        used_indexes.insert(address.key_index);
+                        highest_used_index = highest_used_index.max(Some(address.key_index));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::debug!(
+                            "[SYNC] failed to get the outputs of {}: {}",
+                            address.address().to_bech32(),
+                            e
+                        );
+                        report.failed_addresses.push(address.address().to_bech32());
+                        failed_indexes.insert(address.key_index);
+                    }
+                }
+            }
+            match highest_used_index {
+                Some(key_index) => {
+                    log::debug!("[SYNC] discovered used address {} (internal: {})", key_index, internal);
+                }
+                None => break,
+            }
+        }
+        discovered_addresses.extend(get_addresses_to_keep(
+            generated_addresses,
+            &used_indexes,
+            &failed_indexes,
+        ));
+    }
+    Ok(discovered_addresses)
+}
 
-/// Get the balance and return only addresses with a positive balance
+// Keep the generated addresses up to the last used or failed one, the unused addresses after it aren't needed
+fn get_addresses_to_keep(
+    generated_addresses: Vec<AccountAddress>,
+    used_indexes: &HashSet<usize>,
+    failed_indexes: &HashSet<usize>,
+) -> Vec<AccountAddress> {
+    let highest_index_to_keep = used_indexes.iter().chain(failed_indexes.iter()).max();
+    generated_addresses
+        .into_iter()
+        .filter(|a| Some(&a.key_index) <= highest_index_to_keep)
+        .map(|mut a| {
+            a.used = used_indexes.contains(&a.key_index);
+            a
+        })
+        .collect()
+}
+
+/// Get the balance and return only addresses with a positive balance, the discovered addresses are synced together with
+/// the addresses of the account
 pub(crate) async fn get_addresses_with_balance(
     account_handle: &AccountHandle,
     options: &SyncOptions,
+    discovered_addresses: &[AccountAddress],
+    report: &mut SyncReport,
+    progress: &SyncProgress,
 ) -> crate::Result<Vec<AddressWithBalance>> {
     log::debug!("[SYNC] start get_addresses_with_balance");
     let balance_sync_start_time = Instant::now();
 
     let mut address_before_syncing = account_handle.list_addresses().await?;
+    address_before_syncing.extend(discovered_addresses.iter().cloned());
     // Filter addresses when address_start_index is not 0 so we skip these addresses
     if options.address_start_index != 0 {
         address_before_syncing = address_before_syncing
@@ -31,52 +174,71 @@ pub(crate) async fn get_addresses_with_balance(
             .collect();
     }
 
-    let account = account_handle.read().await;
-    drop(account);
+    // addresses that failed in the previous syncing are requested first
+    let previously_failed = account_handle.last_sync_report.lock().await.failed_addresses.clone();
+    address_before_syncing.sort_by_key(|a| !previously_failed.contains(&a.address().to_bech32()));
 
     log::debug!("[SYNC] sync balance for {} addresses", address_before_syncing.len());
+    report.balance_requests += address_before_syncing.len();
     let client = crate::client::get_client().await?;
     let mut addresses_with_balance = Vec::new();
+    let (mut done, total) = (0, address_before_syncing.len());
+    progress.update(account_handle, SyncPhase::Balances, done, total).await;
     for addresses_chunk in address_before_syncing
         .chunks(PARALLEL_REQUESTS_AMOUNT)
         .map(|x: &[AccountAddress]| x.to_vec())
         .into_iter()
     {
+        done += addresses_chunk.len();
         let mut tasks = Vec::new();
         for address in addresses_chunk {
             let client = client.clone();
             tasks.push(async move {
                 tokio::spawn(async move {
-                    let client = client;
-                    let balance_response = client.get_address().balance(&address.address().to_bech32()).await?;
-                    if balance_response.balance != 0 {
-                        log::debug!(
-                            "[SYNC] found {}i on {}",
-                            balance_response.balance,
-                            address.address().to_bech32()
-                        );
-                    }
-
-                    crate::Result::Ok(AddressWithBalance {
-                        address: address.address,
-                        key_index: address.key_index,
-                        internal: address.internal,
-                        balance: balance_response.balance,
-                        output_ids: Vec::new(),
-                    })
+                    let client = &client;
+                    let bech32_address = &address.address().to_bech32();
+                    let balance_response =
+                        request_with_retries(|| async move { Ok(client.get_address().balance(bech32_address).await?) })
+                            .await;
+                    (address, balance_response)
                 })
                 .await
             });
         }
         let results = futures::future::try_join_all(tasks).await?;
-        for res in results {
-            let address = res?;
+        for (address, balance_response) in results {
+            let balance_response = match balance_response {
+                Ok(balance_response) => balance_response,
+                Err(e) => {
+                    log::debug!(
+                        "[SYNC] failed to get the balance of {}: {}",
+                        address.address().to_bech32(),
+                        e
+                    );
+                    report.failed_addresses.push(address.address().to_bech32());
+                    continue;
+                }
+            };
+            if balance_response.balance != 0 {
+                log::debug!(
+                    "[SYNC] found {}i on {}",
+                    balance_response.balance,
+                    address.address().to_bech32()
+                );
+            }
             // only return addresses with balance or if we discover an account so we don't pass empty addresses around
             // which only slows the process down
-            if address.balance != 0 || options.sync_all_addresses {
-                addresses_with_balance.push(address);
+            if balance_response.balance != 0 || options.sync_all_addresses {
+                addresses_with_balance.push(AddressWithBalance {
+                    address: address.address,
+                    key_index: address.key_index,
+                    internal: address.internal,
+                    balance: balance_response.balance,
+                    output_ids: Vec::new(),
+                });
             }
         }
+        progress.update(account_handle, SyncPhase::Balances, done, total).await;
     }
     log::debug!(
         "[SYNC] finished get_addresses_with_balance in {:.2?}",
@@ -90,6 +252,8 @@ pub(crate) async fn get_address_output_ids(
     account_handle: &AccountHandle,
     options: &SyncOptions,
     addresses_with_balance: Vec<AddressWithBalance>,
+    report: &mut SyncReport,
+    progress: &SyncProgress,
 ) -> crate::Result<(Vec<OutputId>, Vec<AddressWithBalance>)> {
     log::debug!("[SYNC] start get_address_output_ids");
     let address_outputs_sync_start_time = Instant::now();
@@ -100,32 +264,51 @@ pub(crate) async fn get_address_output_ids(
     let (account_index, consolidation_threshold) =
         (account.index, account.account_options.output_consolidation_threshold);
     drop(account);
+    report.output_ids_requests += addresses_with_balance.len();
 
     let mut found_outputs = Vec::new();
     let mut addresses_with_outputs = Vec::new();
+    let (mut done, total) = (0, addresses_with_balance.len());
+    progress.update(account_handle, SyncPhase::OutputIds, done, total).await;
     // We split the addresses into chunks so we don't get timeouts if we have thousands
     for addresses_chunk in &mut addresses_with_balance
         .chunks(PARALLEL_REQUESTS_AMOUNT)
         .map(|x: &[AddressWithBalance]| x.to_vec())
     {
+        done += addresses_chunk.len();
         let mut tasks = Vec::new();
         for address in addresses_chunk {
             let client = client.clone();
             tasks.push(async move {
                 tokio::spawn(async move {
-                    let client = client;
-                    let outputs_response = client
-                        .get_address()
-                        .outputs_response(&address.address().to_bech32(), Default::default())
-                        .await?;
-                    crate::Result::Ok((address, outputs_response))
+                    let client = &client;
+                    let bech32_address = &address.address().to_bech32();
+                    let outputs_response = request_with_retries(|| async move {
+                        Ok(client
+                            .get_address()
+                            .outputs_response(bech32_address, Default::default())
+                            .await?)
+                    })
+                    .await;
+                    (address, outputs_response)
                 })
                 .await
             });
         }
         let results = futures::future::try_join_all(tasks).await?;
-        for res in results {
-            let (mut address, outputs_response): (AddressWithBalance, OutputsAddressResponse) = res?;
+        for (mut address, outputs_response) in results {
+            let outputs_response: OutputsAddressResponse = match outputs_response {
+                Ok(outputs_response) => outputs_response,
+                Err(e) => {
+                    log::debug!(
+                        "[SYNC] failed to get the outputs of {}: {}",
+                        address.address().to_bech32(),
+                        e
+                    );
+                    report.failed_addresses.push(address.address().to_bech32());
+                    continue;
+                }
+            };
             if !outputs_response.output_ids.is_empty() || options.sync_all_addresses {
                 let mut address_outputs = Vec::new();
                 for output_id in &outputs_response.output_ids {
@@ -144,6 +327,7 @@ pub(crate) async fn get_address_output_ids(
                 }
             }
         }
+        progress.update(account_handle, SyncPhase::OutputIds, done, total).await;
     }
     log::debug!(
         "[SYNC] finished get_address_output_ids in {:.2?}",
@@ -152,3 +336,37 @@ pub(crate) async fn get_address_output_ids(
     // addresses with current outputs, historic outputs are ignored
     Ok((found_outputs, addresses_with_outputs))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::get_addresses_to_keep;
+    use crate::account::test_utils::account_address;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn addresses_to_keep() {
+        let generated_addresses: Vec<_> = (0..6).map(|key_index| account_address(1, key_index, false)).collect();
+
+        // nothing used, no address is kept
+        assert!(get_addresses_to_keep(generated_addresses.clone(), &HashSet::new(), &HashSet::new()).is_empty());
+
+        // the addresses up to the last used one are kept, only the used ones are marked as used
+        let used_indexes: HashSet<usize> = [1, 3].into_iter().collect();
+        let kept = get_addresses_to_keep(generated_addresses.clone(), &used_indexes, &HashSet::new());
+        assert_eq!(kept.iter().map(|a| a.key_index).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(
+            kept.iter().map(|a| a.used).collect::<Vec<_>>(),
+            vec![false, true, false, true]
+        );
+
+        // failed addresses after the last used one are kept too, but not as used
+        let failed_indexes: HashSet<usize> = [4].into_iter().collect();
+        let kept = get_addresses_to_keep(generated_addresses, &used_indexes, &failed_indexes);
+        assert_eq!(
+            kept.iter().map(|a| a.key_index).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+        assert!(!kept[4].used);
+    }
+}
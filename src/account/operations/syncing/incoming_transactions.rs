@@ -0,0 +1,251 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::PARALLEL_REQUESTS_AMOUNT,
+    handle::AccountHandle,
+    operations::syncing::{requests::request_with_retries, timestamps::get_message_timestamps, SyncReport},
+    types::{InclusionState, OutputData, Transaction},
+    Account,
+};
+
+use crypto::hashes::{blake2b::Blake2b256, Digest};
+use iota_client::{
+    bee_message::{
+        address::{Address, Ed25519Address},
+        output::Output,
+        payload::{
+            transaction::{Essence, TransactionId, TransactionPayload},
+            Payload,
+        },
+        signature::SignatureUnlock,
+        unlock::UnlockBlock,
+        MessageId,
+    },
+    Client,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Get the transactions which created new outputs of the account and weren't sent by the account. Transactions that
+/// couldn't be requested are skipped.
+pub(crate) async fn get_incoming_transactions(
+    account_handle: &AccountHandle,
+    outputs: &[OutputData],
+    report: &mut SyncReport,
+) -> crate::Result<Vec<Transaction>> {
+    let account = account_handle.read().await;
+    let transaction_ids: HashSet<TransactionId> = outputs
+        .iter()
+        .map(|output| *output.output_id.transaction_id())
+        .filter(|transaction_id| !account.transactions.contains_key(transaction_id))
+        .collect();
+    drop(account);
+    if transaction_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    log::debug!("[SYNC] get {} incoming transactions", transaction_ids.len());
+    report.transaction_requests += transaction_ids.len();
+
+    let client = crate::client::get_client().await?;
+    let mut incoming_payloads = Vec::new();
+    for transaction_ids_chunk in transaction_ids
+        .into_iter()
+        .collect::<Vec<TransactionId>>()
+        .chunks(PARALLEL_REQUESTS_AMOUNT)
+        .map(|x: &[TransactionId]| x.to_vec())
+    {
+        let mut tasks = Vec::new();
+        for transaction_id in transaction_ids_chunk {
+            let client = client.clone();
+            tasks.push(async move {
+                tokio::spawn(async move {
+                    let client = &client;
+                    let transaction =
+                        request_with_retries(|| async move { get_included_transaction(client, transaction_id).await })
+                            .await;
+                    (transaction_id, transaction)
+                })
+                .await
+            });
+        }
+        let results = futures::future::try_join_all(tasks).await?;
+        for (transaction_id, transaction) in results {
+            match transaction {
+                Ok(transaction) => incoming_payloads.push(transaction),
+                // the outputs are stored anyway, only the transaction with the senders is missing, for example if
+                // the node pruned it
+                Err(e) => log::debug!("[SYNC] failed to get incoming transaction {}: {}", transaction_id, e),
+            }
+        }
+    }
+
+    let network_id = client.get_network_id().await?;
+    let bech32_hrp = client.get_bech32_hrp().await?;
+    let mut incoming_transactions = {
+        let account = account_handle.read().await;
+        incoming_payloads
+            .into_iter()
+            .map(|(message_id, payload)| incoming_transaction(&account, payload, message_id, network_id, &bech32_hrp))
+            .collect::<crate::Result<Vec<Transaction>>>()?
+    };
+
+    // date the transactions with the milestone that confirmed them
+    let timestamps = get_message_timestamps(
+        incoming_transactions.iter().filter_map(|t| t.message_id).collect(),
+        report,
+    )
+    .await?;
+    for transaction in incoming_transactions.iter_mut() {
+        if let Some((timestamp, timestamp_source)) = transaction.message_id.and_then(|id| timestamps.get(&id)) {
+            transaction.timestamp = *timestamp;
+            transaction.timestamp_source = *timestamp_source;
+        }
+    }
+    Ok(incoming_transactions)
+}
+
+// Get the confirmed message of a transaction and its payload
+async fn get_included_transaction(
+    client: &Client,
+    transaction_id: TransactionId,
+) -> crate::Result<(MessageId, TransactionPayload)> {
+    let message = client.get_included_message(&transaction_id).await?;
+    match message.payload() {
+        Some(Payload::Transaction(payload)) => Ok((message.id().0, payload.as_ref().clone())),
+        _ => Err(crate::Error::TransactionNotFound(transaction_id)),
+    }
+}
+
+// Create the confirmed incoming transaction with the senders and the amount that was sent to the account
+fn incoming_transaction(
+    account: &Account,
+    payload: TransactionPayload,
+    message_id: MessageId,
+    network_id: u64,
+    bech32_hrp: &str,
+) -> crate::Result<Transaction> {
+    let Essence::Regular(essence) = payload.essence();
+    let received_amount = essence
+        .outputs()
+        .iter()
+        .map(|output| match output {
+            Output::SignatureLockedSingle(output) if account.is_account_address(output.address()) => output.amount(),
+            Output::SignatureLockedDustAllowance(output) if account.is_account_address(output.address()) => {
+                output.amount()
+            }
+            _ => 0,
+        })
+        .sum();
+
+    Ok(Transaction {
+        senders: get_senders(&payload, bech32_hrp)?,
+        received_amount: Some(received_amount),
+        conflict_reason: None,
+        payload,
+        message_id: Some(message_id),
+        inclusion_state: InclusionState::Confirmed,
+        // set from the milestone afterwards
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_millis(),
+        timestamp_source: Default::default(),
+        network_id,
+        incoming: true,
+        internal: false,
+        replaces: None,
+        replaced_by: None,
+        reattachments: 0,
+        last_reattachment: None,
+        annotation: Default::default(),
+        contacts: HashMap::new(),
+    })
+}
+
+// Get the bech32 addresses which signed the inputs of a transaction
+fn get_senders(transaction_payload: &TransactionPayload, bech32_hrp: &str) -> crate::Result<Vec<String>> {
+    let mut senders = Vec::new();
+    for unlock_block in transaction_payload.unlock_blocks().iter() {
+        // reference unlock blocks use the address of a previous signature
+        if let UnlockBlock::Signature(SignatureUnlock::Ed25519(signature)) = unlock_block {
+            let public_key_hash = Blake2b256::digest(signature.public_key())
+                .try_into()
+                .map_err(|_e| crate::Error::Blake2b256("Hashing the public key of a sender failed."))?;
+            let address = Address::Ed25519(Ed25519Address::new(public_key_hash)).to_bech32(bech32_hrp);
+            if !senders.contains(&address) {
+                senders.push(address);
+            }
+        }
+    }
+    Ok(senders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::incoming_transaction;
+    use crate::account::{
+        test_utils::{account, account_address, address, output_id, transaction},
+        types::InclusionState,
+    };
+
+    use crypto::hashes::{blake2b::Blake2b256, Digest};
+    use iota_client::bee_message::{
+        address::{Address, Ed25519Address},
+        payload::transaction::TransactionPayload,
+        signature::{Ed25519Signature, SignatureUnlock},
+        unlock::{ReferenceUnlock, UnlockBlock, UnlockBlocks},
+        MessageId,
+    };
+
+    // The bech32 address of the public key
+    fn sender(public_key: [u8; 32]) -> String {
+        let public_key_hash = Blake2b256::digest(&public_key).try_into().unwrap();
+        Address::Ed25519(Ed25519Address::new(public_key_hash)).to_bech32("atoi")
+    }
+
+    #[test]
+    fn incoming_transaction_senders_and_amount() {
+        let mut account = account();
+        let public_address = account_address(1, 0, false);
+        let internal_address = account_address(2, 0, true);
+        account.public_addresses = vec![public_address.clone()];
+        account.internal_addresses = vec![internal_address.clone()];
+
+        let payload = transaction(
+            &[output_id(1, 0), output_id(1, 1), output_id(1, 2)],
+            &[
+                (public_address.address.inner, 1_000_000),
+                (internal_address.address.inner, 2_000_000),
+                (address(3), 3_000_000),
+            ],
+        )
+        .payload;
+        // two inputs are signed by the same key, the reference unlock block doesn't add another sender
+        let signature =
+            |public_key| UnlockBlock::Signature(SignatureUnlock::Ed25519(Ed25519Signature::new(public_key, [0; 64])));
+        let payload = TransactionPayload::builder()
+            .with_essence(payload.essence().clone())
+            .with_unlock_blocks(
+                UnlockBlocks::new(vec![
+                    signature([1; 32]),
+                    UnlockBlock::Reference(ReferenceUnlock::new(0).unwrap()),
+                    signature([2; 32]),
+                ])
+                .unwrap(),
+            )
+            .finish()
+            .unwrap();
+
+        let transaction = incoming_transaction(&account, payload, MessageId::new([1; 32]), 0, "atoi").unwrap();
+        assert_eq!(transaction.senders, vec![sender([1; 32]), sender([2; 32])]);
+        // only the outputs to public and internal addresses of the account are received
+        assert_eq!(transaction.received_amount, Some(3_000_000));
+        assert_eq!(transaction.message_id, Some(MessageId::new([1; 32])));
+        assert_eq!(transaction.inclusion_state, InclusionState::Confirmed);
+        assert!(transaction.incoming);
+    }
+}
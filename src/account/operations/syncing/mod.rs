@@ -2,28 +2,48 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub(crate) mod addresses;
+pub(crate) mod incoming_transactions;
 pub mod options;
 pub(crate) mod outputs;
+pub mod progress;
+pub mod report;
+pub(crate) mod requests;
+pub(crate) mod timestamps;
 pub(crate) mod transactions;
 use crate::account::{
     constants::MIN_SYNC_INTERVAL,
     handle::AccountHandle,
-    operations::output_consolidation::consolidate_outputs,
-    types::{address::AddressWithBalance, InclusionState, OutputData, Transaction},
-    AccountBalance,
+    operations::{
+        output_consolidation::consolidate_outputs,
+        output_locks::{save_output_locks, unlock_outputs},
+        transfer::replace_transaction::resend_transaction,
+    },
+    types::{
+        address::{AccountAddress, AddressWithBalance},
+        InclusionState, OutputData, Transaction,
+    },
+    Account, AccountBalance,
 };
+#[cfg(feature = "events")]
+use crate::events::types::{IncomingTransactionEvent, TransactionInclusionEvent, WalletEvent};
 #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
 use crate::signing::SignerType;
-pub use options::SyncOptions;
+pub use options::{SyncCancellation, SyncOptions};
+pub use progress::SyncPhase;
+pub use report::SyncReport;
 
-use iota_client::bee_message::output::OutputId;
+use iota_client::bee_message::{output::OutputId, payload::transaction::TransactionId};
+use progress::SyncProgress;
 
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-/// Syncs an account
+/// Syncs an account. If the syncing gets cancelled with the cancellation token of the options, it stops at the next
+/// phase boundary with [`crate::Error::SyncCancelled`] and nothing is stored.
 pub async fn sync_account(account_handle: &AccountHandle, options: &SyncOptions) -> crate::Result<AccountBalance> {
     log::debug!("[SYNC] start syncing with {:?}", options);
     let syc_start_time = Instant::now();
+    let mut report = SyncReport::default();
+    let progress = SyncProgress::new(syc_start_time);
 
     // prevent syncing the account multiple times simultaneously
     let time_now = SystemTime::now()
@@ -43,23 +63,69 @@ pub async fn sync_account(account_handle: &AccountHandle, options: &SyncOptions)
 
     // sync transactions first so we maybe get confirmed outputs in the syncing process later
     // do we want a field in SyncOptions so it can be skipped?
-    let (synced_transactions, spent_output_ids) = transactions::sync_transactions(account_handle).await?;
+    let pending_transactions = account_handle.read().await.pending_transactions.len();
+    progress
+        .update(account_handle, SyncPhase::Transactions, 0, pending_transactions)
+        .await;
+    let (synced_transactions, mut spent_output_ids, unspent_input_ids) =
+        transactions::sync_transactions(account_handle, None, &mut report).await?;
+    progress
+        .update(
+            account_handle,
+            SyncPhase::Transactions,
+            pending_transactions,
+            pending_transactions,
+        )
+        .await;
+    check_cancellation(options)?;
+
+    // find funds on addresses after the highest known index, the addresses are stored with the synced data
+    let discovered_addresses = if options.gap_limit != 0 {
+        let discovered_addresses = addresses::discover_addresses(account_handle, options, &mut report).await?;
+        check_cancellation(options)?;
+        discovered_addresses
+    } else {
+        Vec::new()
+    };
 
     // we get the balance first because it's a less heavy operation for the nodes
-    let addresses_with_balance = addresses::get_addresses_with_balance(account_handle, options).await?;
+    let addresses_with_balance =
+        addresses::get_addresses_with_balance(account_handle, options, &discovered_addresses, &mut report, &progress)
+            .await?;
     log::debug!("[SYNC] found {} addresses_with_balance", addresses_with_balance.len());
+    check_cancellation(options)?;
 
     // get outputs only for addresses that have > 0 as balance and add them also the the addresses_with_balance
-    let (new_output_ids, addresses_with_balance) =
-        addresses::get_address_output_ids(account_handle, options, addresses_with_balance.clone()).await?;
+    let (found_output_ids, addresses_with_balance) = addresses::get_address_output_ids(
+        account_handle,
+        options,
+        addresses_with_balance.clone(),
+        &mut report,
+        &progress,
+    )
+    .await?;
+    check_cancellation(options)?;
 
-    let output_responses = outputs::get_outputs(account_handle, options, new_output_ids.clone()).await?;
-    let outputs = outputs::output_response_to_output_data(account_handle, output_responses).await?;
+    // only request outputs we don't know yet and known unspent outputs that the node didn't return anymore
+    let (new_output_ids, missing_output_ids) =
+        outputs::diff_output_ids(account_handle, options, &found_output_ids, &mut report).await?;
+    let output_responses =
+        outputs::get_outputs(account_handle, options, new_output_ids, &mut report, &progress).await?;
+    let outputs = outputs::output_response_to_output_data(account_handle, output_responses, &mut report).await?;
+    spent_output_ids.extend(
+        outputs::get_spent_output_ids(account_handle, options, missing_output_ids, &mut report, &progress).await?,
+    );
+    let incoming_transactions =
+        incoming_transactions::get_incoming_transactions(account_handle, &outputs, &mut report).await?;
+    check_cancellation(options)?;
 
     // only when actively called or also in the background syncing?
-    let signer_type = {
+    let (signer_type, automatic_output_consolidation) = {
         let account = account_handle.read().await;
-        account.signer_type.clone()
+        (
+            account.signer_type.clone(),
+            account.account_options.automatic_output_consolidation,
+        )
     };
     match signer_type {
         #[cfg(feature = "ledger-nano")]
@@ -68,22 +134,28 @@ pub async fn sync_account(account_handle: &AccountHandle, options: &SyncOptions)
         #[cfg(feature = "ledger-nano-simulator")]
         SignerType::LedgerNanoSimulator => {}
         _ => {
-            consolidate_outputs(account_handle).await?;
+            if automatic_output_consolidation {
+                consolidate_outputs(account_handle).await?;
+            }
         }
     };
 
-    // add a field to the sync options to also sync incoming transactions?
-
     // update account with balances, output ids, outputs
+    let transactions_to_resend = get_transactions_to_resend(&synced_transactions);
     update_account(
         account_handle,
+        discovered_addresses,
         addresses_with_balance,
         outputs,
         synced_transactions,
+        incoming_transactions,
         spent_output_ids,
+        unspent_input_ids,
+        &report.failed_addresses,
         options,
     )
     .await?;
+    resend_conflicting_transactions(account_handle, transactions_to_resend).await;
     // store account with storage feature
 
     let account_balance = account_handle.balance().await?;
@@ -93,20 +165,52 @@ pub async fn sync_account(account_handle: &AccountHandle, options: &SyncOptions)
         .expect("Time went backwards")
         .as_millis();
     *last_synced = time_now;
-    log::debug!("[SYNC] finished syncing in {:.2?}", syc_start_time.elapsed());
+    report.duration = syc_start_time.elapsed().as_millis();
+    log::debug!(
+        "[SYNC] finished syncing in {:.2?} {:?}",
+        syc_start_time.elapsed(),
+        report
+    );
+    *account_handle.last_sync_report.lock().await = report;
     Ok(account_balance)
 }
 
+// Stop the syncing if it got cancelled, so the synced data isn't stored
+fn check_cancellation(options: &SyncOptions) -> crate::Result<()> {
+    match &options.cancellation {
+        Some(cancellation) if cancellation.is_cancelled() => {
+            log::debug!("[SYNC] syncing cancelled");
+            Err(crate::Error::SyncCancelled)
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Update account with newly synced data
 async fn update_account(
     account_handle: &AccountHandle,
+    discovered_addresses: Vec<AccountAddress>,
     addresses_with_balance: Vec<AddressWithBalance>,
     outputs: Vec<OutputData>,
     synced_transactions: Vec<Transaction>,
+    incoming_transactions: Vec<Transaction>,
     spent_output_ids: Vec<OutputId>,
+    unspent_input_ids: Vec<OutputId>,
+    failed_addresses: &[String],
     options: &SyncOptions,
 ) -> crate::Result<()> {
     let mut account = account_handle.write().await;
+    // add the discovered addresses, unless an address with the same index was generated in the meantime
+    for address in discovered_addresses {
+        let addresses = if address.internal {
+            &mut account.internal_addresses
+        } else {
+            &mut account.public_addresses
+        };
+        if address.key_index == addresses.len() {
+            addresses.push(address);
+        }
+    }
     // update used field of the addresses
     for address in addresses_with_balance.iter() {
         if address.internal {
@@ -124,27 +228,168 @@ async fn update_account(
         }
     }
     // get all addresses with balance that we didn't sync because their index is below the address_start_index of the
-    // options
+    // options or because their requests failed
     account.addresses_with_balance = account
         .addresses_with_balance
         .iter()
-        .filter(|a| a.key_index < options.address_start_index)
+        .filter(|a| a.key_index < options.address_start_index || failed_addresses.contains(&a.address.to_bech32()))
         .cloned()
         .collect();
     // then add all synced addresses with balance
     account.addresses_with_balance.extend(addresses_with_balance);
 
-    for output in outputs {
+    for mut output in outputs {
+        // keep the local annotation of known outputs
+        if let Some(known_output) = account.outputs.get(&output.output_id) {
+            output.annotation = known_output.annotation.clone();
+        }
         account.outputs.insert(output.output_id, output.clone());
         if !output.is_spent {
             account.unspent_outputs.insert(output.output_id, output);
         }
     }
 
-    for transaction in synced_transactions {
+    for transaction in incoming_transactions {
+        log::debug!("[SYNC] incoming transaction {}", transaction.payload.id());
+        #[cfg(feature = "events")]
+        account_handle.event_emitter.lock().await.emit(
+            account.index,
+            WalletEvent::IncomingTransaction(IncomingTransactionEvent {
+                transaction_id: transaction.payload.id(),
+                amount: transaction.received_amount.unwrap_or_default(),
+                senders: transaction.senders.clone(),
+            }),
+        );
+        account.transactions.insert(transaction.payload.id(), transaction);
+    }
+
+    update_transactions(
+        account_handle,
+        &mut account,
+        synced_transactions,
+        spent_output_ids,
+        unspent_input_ids,
+    )
+    .await;
+    #[cfg(feature = "storage")]
+    log::debug!("[SYNC] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    save_output_locks(&account).await?;
+    // println!("{:#?}", account);
+    Ok(())
+}
+
+/// Syncs a single pending transaction and updates the account with the result
+pub(crate) async fn sync_pending_transaction(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+) -> crate::Result<()> {
+    let (synced_transactions, spent_output_ids, unspent_input_ids) =
+        transactions::sync_transactions(account_handle, Some(transaction_id), &mut SyncReport::default()).await?;
+    let transactions_to_resend = get_transactions_to_resend(&synced_transactions);
+    let mut account = account_handle.write().await;
+    update_transactions(
+        account_handle,
+        &mut account,
+        synced_transactions,
+        spent_output_ids,
+        unspent_input_ids,
+    )
+    .await;
+    #[cfg(feature = "storage")]
+    log::debug!("[SYNC] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    save_output_locks(&account).await?;
+    drop(account);
+    resend_conflicting_transactions(account_handle, transactions_to_resend).await;
+    Ok(())
+}
+
+// Get the conflicting transactions whose inputs were spent by another transaction, they can be sent again with other
+// inputs. Replacements and cancellations aren't sent again, because the transaction they replaced could be confirmed.
+fn get_transactions_to_resend(synced_transactions: &[Transaction]) -> Vec<TransactionId> {
+    synced_transactions
+        .iter()
+        .filter(|transaction| {
+            transaction.inclusion_state == InclusionState::Conflicting
+                && !transaction.incoming
+                && transaction.replaces.is_none()
+                && transaction.replaced_by.is_none()
+                && transaction
+                    .conflict_reason
+                    .map_or(false, |conflict_reason| conflict_reason.is_input_conflict())
+        })
+        .map(|transaction| transaction.payload.id())
+        .collect()
+}
+
+// Send conflicting transactions again if the reattach policy allows it, failures are only logged so they don't fail
+// the syncing
+async fn resend_conflicting_transactions(account_handle: &AccountHandle, transaction_ids: Vec<TransactionId>) {
+    if transaction_ids.is_empty()
+        || !account_handle
+            .read()
+            .await
+            .account_options
+            .reattach_policy
+            .resend_conflicting
+    {
+        return;
+    }
+    for transaction_id in transaction_ids {
+        match resend_transaction(account_handle, transaction_id).await {
+            Ok(transfer_result) => log::debug!(
+                "[SYNC] resent conflicting transaction {} as {}",
+                transaction_id,
+                transfer_result.transaction_id
+            ),
+            Err(e) => log::debug!(
+                "[SYNC] failed to resend conflicting transaction {}: {}",
+                transaction_id,
+                e
+            ),
+        }
+    }
+}
+
+// Update the account with synced transactions and mark the spent outputs as spent
+async fn update_transactions(
+    account_handle: &AccountHandle,
+    account: &mut Account,
+    synced_transactions: Vec<Transaction>,
+    spent_output_ids: Vec<OutputId>,
+    unspent_input_ids: Vec<OutputId>,
+) {
+    for mut transaction in synced_transactions {
+        // the annotation could have been changed during syncing
+        if let Some(known_transaction) = account.transactions.get(&transaction.payload.id()) {
+            transaction.annotation = known_transaction.annotation.clone();
+        }
         match transaction.inclusion_state {
             InclusionState::Confirmed | InclusionState::Conflicting => {
-                account.pending_transactions.remove(&transaction.payload.id());
+                let was_pending = account.pending_transactions.remove(&transaction.payload.id());
+                account_handle.retry_tasks.abort(&transaction.payload.id());
+                #[cfg(feature = "events")]
+                if was_pending {
+                    account_handle.event_emitter.lock().await.emit(
+                        account.index,
+                        WalletEvent::TransactionInclusion(TransactionInclusionEvent {
+                            transaction_id: transaction.payload.id(),
+                            inclusion_state: transaction.inclusion_state.clone(),
+                            conflict_reason: transaction.conflict_reason,
+                        }),
+                    );
+                }
             }
             _ => {}
         }
@@ -159,19 +404,17 @@ async fn update_account(
             output.is_spent = true;
         }
         account.locked_outputs.remove(&spent_output_id);
+        account.output_locks.remove(&spent_output_id);
+        account.frozen_outputs.remove(&spent_output_id);
         account.unspent_outputs.remove(&spent_output_id);
         log::debug!("[SYNC] Unlocked {}", spent_output_id);
     }
-    #[cfg(feature = "storage")]
-    log::debug!("[SYNC] storing account {}", account.index());
-    crate::storage::manager::get()
-        .await?
-        .lock()
-        .await
-        .save_account(&account)
-        .await?;
-    // println!("{:#?}", account);
-    Ok(())
+
+    // the inputs of conflicting transactions that weren't spent by another transaction are available again
+    if !unspent_input_ids.is_empty() {
+        log::debug!("[SYNC] Unlocked unspent inputs {:?}", unspent_input_ids);
+        unlock_outputs(account, &unspent_input_ids);
+    }
 }
 
 // have an own function to sync spent outputs? (only for history reasons, not important now)
@@ -181,3 +424,55 @@ async fn update_account(
 // ) -> crate::Result<Vec<Output>> {
 //     Ok(vec![])
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::{check_cancellation, get_transactions_to_resend, SyncCancellation, SyncOptions};
+    use crate::account::{
+        test_utils::{address, output_id, transaction},
+        types::{ConflictReason, InclusionState, Transaction},
+    };
+
+    // An outgoing conflicting transaction, the amount makes the transaction id unique
+    fn conflicting_transaction(amount: u64, conflict_reason: ConflictReason) -> Transaction {
+        let mut transaction = transaction(&[output_id(0, 0)], &[(address(0), amount)]);
+        transaction.inclusion_state = InclusionState::Conflicting;
+        transaction.conflict_reason.replace(conflict_reason);
+        transaction
+    }
+
+    #[test]
+    fn transactions_to_resend() {
+        let resendable = conflicting_transaction(1_000_000, ConflictReason::InputUtxoAlreadySpent);
+        let invalid_signature = conflicting_transaction(2_000_000, ConflictReason::InvalidSignature);
+        let mut replacement = conflicting_transaction(3_000_000, ConflictReason::InputUtxoNotFound);
+        replacement.replaces.replace(resendable.payload.id());
+        let mut replaced = conflicting_transaction(4_000_000, ConflictReason::InputUtxoAlreadySpent);
+        replaced.replaced_by.replace(resendable.payload.id());
+        let mut incoming = conflicting_transaction(5_000_000, ConflictReason::InputUtxoAlreadySpent);
+        incoming.incoming = true;
+        let mut pending = conflicting_transaction(6_000_000, ConflictReason::InputUtxoAlreadySpent);
+        pending.inclusion_state = InclusionState::Pending;
+
+        let resendable_id = resendable.payload.id();
+        assert_eq!(
+            get_transactions_to_resend(&[resendable, invalid_signature, replacement, replaced, incoming, pending]),
+            vec![resendable_id]
+        );
+    }
+
+    #[test]
+    fn sync_cancellation() {
+        assert!(check_cancellation(&SyncOptions::default()).is_ok());
+
+        let cancellation = SyncCancellation::new();
+        let options = SyncOptions {
+            cancellation: Some(cancellation.clone()),
+            ..Default::default()
+        };
+        assert!(check_cancellation(&options).is_ok());
+        // cancelling a clone cancels the syncing with the options
+        cancellation.cancel();
+        assert!(matches!(check_cancellation(&options), Err(crate::Error::SyncCancelled)));
+    }
+}
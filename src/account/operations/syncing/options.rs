@@ -3,6 +3,11 @@
 
 use serde::{Deserialize, Serialize};
 
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 /// The synchronization options
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SyncOptions {
@@ -20,7 +25,8 @@ pub struct SyncOptions {
     // lower index aren't synced
     #[serde(rename = "addressStartIndex", default = "default_address_start_index")]
     pub address_start_index: usize,
-    // 0 by default, no new address should be generated during syncing
+    // 0 by default, no new address should be generated during syncing. With a higher value, public and internal
+    // addresses after the highest known index are checked for outputs, until `gap_limit` addresses in a row are unused
     #[serde(rename = "gapLimit", default = "default_gap_limit")]
     pub gap_limit: usize,
     #[serde(rename = "syncSpentOutputs", default)]
@@ -33,6 +39,30 @@ pub struct SyncOptions {
     // usually we skip syncing if it's called within a few ms, but if we change the client options we need to resync
     #[serde(rename = "forceSyncing", default)]
     pub force_syncing: bool,
+    // Cancels the syncing at the next phase boundary, nothing synced until then is stored
+    #[serde(skip)]
+    pub cancellation: Option<SyncCancellation>,
+}
+
+/// Token to cancel a running syncing, clones cancel the same syncing
+#[derive(Debug, Clone, Default)]
+pub struct SyncCancellation(Arc<AtomicBool>);
+
+impl SyncCancellation {
+    /// Create a new token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the syncing, it stops with [`crate::Error::SyncCancelled`]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the syncing got cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
 }
 
 fn default_output_consolidation_threshold() -> usize {
@@ -4,8 +4,12 @@
 use crate::account::{
     constants::PARALLEL_REQUESTS_AMOUNT,
     handle::AccountHandle,
-    operations::syncing::SyncOptions,
+    operations::syncing::{
+        progress::SyncProgress, requests::request_with_retries, timestamps::get_message_timestamps, SyncOptions,
+        SyncPhase, SyncReport,
+    },
     types::{OutputData, OutputKind},
+    Account,
 };
 
 use iota_client::{
@@ -22,17 +26,20 @@ use iota_client::{
     },
 };
 
-use std::{
-    str::FromStr,
-    time::{Instant, SystemTime, UNIX_EPOCH},
-};
+use std::{collections::HashSet, str::FromStr, time::Instant};
 
-/// Convert OutputResponse to OutputData with the network_id added
+/// Convert OutputResponse to OutputData with the network_id and the timestamp of the milestone that confirmed it
 pub(crate) async fn output_response_to_output_data(
     account_handle: &AccountHandle,
     output_responses: Vec<OutputResponse>,
+    report: &mut SyncReport,
 ) -> crate::Result<Vec<OutputData>> {
     log::debug!("[SYNC] convert output_responses");
+    let message_ids = output_responses
+        .iter()
+        .map(|output| MessageId::from_str(&output.message_id))
+        .collect::<Result<HashSet<MessageId>, _>>()?;
+    let timestamps = get_message_timestamps(message_ids, report).await?;
     // store outputs with network_id
     let account = account_handle.read().await;
     let client = crate::client::get_client().await?;
@@ -51,32 +58,124 @@ pub(crate) async fn output_response_to_output_data(
                     None => false,
                 }
             };
+            let message_id = MessageId::from_str(&output.message_id)?;
+            let (timestamp, timestamp_source) = timestamps[&message_id];
             Ok(OutputData {
                 output_id: OutputId::new(transaction_id, output.output_index)?,
-                message_id: MessageId::from_str(&output.message_id)?,
+                message_id,
                 amount,
                 is_spent: output.is_spent,
                 address,
                 kind: output_kind,
                 network_id,
                 remainder,
-                // get this from the milestone that confirmed the message with the transaction?
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_millis(),
+                timestamp,
+                timestamp_source,
+                annotation: Default::default(),
             })
         })
         .collect::<crate::Result<Vec<OutputData>>>()
 }
 
-/// Get the current output ids for provided addresses
-pub(crate) async fn get_outputs(
+/// Compare the output ids from the node with the known outputs. Returns the output ids that aren't known yet and
+/// the known unspent outputs of synced addresses that weren't returned by the node anymore, so they are probably spent
+pub(crate) async fn diff_output_ids(
+    account_handle: &AccountHandle,
+    options: &SyncOptions,
+    found_output_ids: &[OutputId],
+    report: &mut SyncReport,
+) -> crate::Result<(Vec<OutputId>, Vec<OutputId>)> {
+    let network_id = crate::client::get_client().await?.get_network_id().await?;
+    Ok(get_output_ids_diff(
+        &account_handle.read().await,
+        options,
+        network_id,
+        found_output_ids,
+        report,
+    ))
+}
+
+fn get_output_ids_diff(
+    account: &Account,
+    options: &SyncOptions,
+    network_id: u64,
+    found_output_ids: &[OutputId],
+    report: &mut SyncReport,
+) -> (Vec<OutputId>, Vec<OutputId>) {
+    let found_output_ids: HashSet<&OutputId> = found_output_ids.iter().collect();
+
+    let new_output_ids: Vec<OutputId> = found_output_ids
+        .iter()
+        .filter(|output_id| !account.unspent_outputs.contains_key(output_id))
+        .map(|output_id| **output_id)
+        .collect();
+    report.skipped_outputs += found_output_ids.len() - new_output_ids.len();
+
+    // addresses below the address_start_index or with failed requests aren't synced, so their outputs can't be missing
+    let synced_addresses: HashSet<&Address> = account
+        .public_addresses
+        .iter()
+        .chain(account.internal_addresses.iter())
+        .filter(|a| {
+            a.key_index >= options.address_start_index && !report.failed_addresses.contains(&a.address.to_bech32())
+        })
+        .map(|a| &a.address.inner)
+        .collect();
+    let missing_output_ids: Vec<OutputId> = account
+        .unspent_outputs
+        .values()
+        .filter(|output| {
+            output.network_id == network_id
+                && synced_addresses.contains(&output.address)
+                && !found_output_ids.contains(&output.output_id)
+        })
+        .map(|output| output.output_id)
+        .collect();
+    log::debug!(
+        "[SYNC] {} new outputs, {} known outputs, missing outputs: {:?}",
+        new_output_ids.len(),
+        found_output_ids.len() - new_output_ids.len(),
+        missing_output_ids
+    );
+    (new_output_ids, missing_output_ids)
+}
+
+/// Request outputs that were unspent, but aren't returned for their address anymore and return the spent ones
+pub(crate) async fn get_spent_output_ids(
     account_handle: &AccountHandle,
     options: &SyncOptions,
     output_ids: Vec<OutputId>,
+    report: &mut SyncReport,
+    progress: &SyncProgress,
+) -> crate::Result<Vec<OutputId>> {
+    if output_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut spent_output_ids = Vec::new();
+    for output in get_outputs(account_handle, options, output_ids, report, progress).await? {
+        if output.is_spent {
+            spent_output_ids.push(OutputId::new(
+                TransactionId::from_str(&output.transaction_id)?,
+                output.output_index,
+            )?);
+        }
+    }
+    Ok(spent_output_ids)
+}
+
+/// Get the outputs for the provided output ids
+pub(crate) async fn get_outputs(
+    account_handle: &AccountHandle,
+    options: &SyncOptions,
+    mut output_ids: Vec<OutputId>,
+    report: &mut SyncReport,
+    progress: &SyncProgress,
 ) -> crate::Result<Vec<OutputResponse>> {
     log::debug!("[SYNC] start get_outputs");
+    report.output_requests += output_ids.len();
+    // outputs that failed in the previous syncing are requested first
+    let previously_failed = account_handle.last_sync_report.lock().await.failed_outputs.clone();
+    output_ids.sort_by_key(|output_id| !previously_failed.contains(output_id));
     let get_outputs_sync_start_time = Instant::now();
     let account = account_handle.read().await;
 
@@ -84,29 +183,42 @@ pub(crate) async fn get_outputs(
     drop(account);
 
     let mut found_outputs = Vec::new();
+    let (mut done, total) = (0, output_ids.len());
+    progress.update(account_handle, SyncPhase::Outputs, done, total).await;
     // We split the outputs into chunks so we don't get timeouts if we have thousands
     for output_ids_chunk in output_ids
         .chunks(PARALLEL_REQUESTS_AMOUNT)
         .map(|x: &[OutputId]| x.to_vec())
         .into_iter()
     {
+        done += output_ids_chunk.len();
         let mut tasks = Vec::new();
         for output_id in output_ids_chunk {
             let client = client.clone();
             tasks.push(async move {
                 tokio::spawn(async move {
-                    let client = client;
-                    let output = client.get_output(&UtxoInput::from(output_id)).await?;
-                    crate::Result::Ok(output)
+                    let client = &client;
+                    let output =
+                        request_with_retries(
+                            || async move { Ok(client.get_output(&UtxoInput::from(output_id)).await?) },
+                        )
+                        .await;
+                    (output_id, output)
                 })
                 .await
             });
         }
         let results = futures::future::try_join_all(tasks).await?;
-        for res in results {
-            let output = res?;
-            found_outputs.push(output);
+        for (output_id, output) in results {
+            match output {
+                Ok(output) => found_outputs.push(output),
+                Err(e) => {
+                    log::debug!("[SYNC] failed to get output {}: {}", output_id, e);
+                    report.failed_outputs.push(output_id);
+                }
+            }
         }
+        progress.update(account_handle, SyncPhase::Outputs, done, total).await;
     }
 
     log::debug!(
@@ -134,3 +246,52 @@ pub(crate) fn get_output_amount_and_address(output: &OutputDto) -> crate::Result
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::get_output_ids_diff;
+    use crate::account::{
+        operations::syncing::{SyncOptions, SyncReport},
+        test_utils::{account, account_address, output, output_id},
+    };
+
+    #[test]
+    fn output_ids_diff() {
+        let mut account = account();
+        let synced_address = account_address(1, 1, false);
+        let skipped_address = account_address(2, 0, false);
+        let failed_address = account_address(3, 2, false);
+        for (output_id, address) in [
+            (output_id(1, 0), &synced_address),
+            (output_id(1, 1), &synced_address),
+            (output_id(1, 2), &skipped_address),
+            (output_id(1, 3), &failed_address),
+        ] {
+            account
+                .unspent_outputs
+                .insert(output_id, output(output_id, address.address.inner, 1_000_000));
+        }
+        // outputs of another network aren't returned by the node
+        let mut other_network_output = output(output_id(1, 4), synced_address.address.inner, 1_000_000);
+        other_network_output.network_id = 1;
+        account
+            .unspent_outputs
+            .insert(other_network_output.output_id, other_network_output);
+        account.public_addresses = vec![skipped_address, synced_address, failed_address.clone()];
+
+        let options = SyncOptions {
+            address_start_index: 1,
+            ..Default::default()
+        };
+        let mut report = SyncReport {
+            failed_addresses: vec![failed_address.address.to_bech32()],
+            ..Default::default()
+        };
+        let (new_output_ids, missing_output_ids) =
+            get_output_ids_diff(&account, &options, 0, &[output_id(1, 0), output_id(2, 0)], &mut report);
+        assert_eq!(new_output_ids, vec![output_id(2, 0)]);
+        // only the unspent outputs of synced addresses can be missing
+        assert_eq!(missing_output_ids, vec![output_id(1, 1)]);
+        assert_eq!(report.skipped_outputs, 1);
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::handle::AccountHandle;
+#[cfg(feature = "events")]
+use crate::events::types::{SyncProgressEvent, WalletEvent};
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Instant;
+
+/// The phases of a syncing which report their progress
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SyncPhase {
+    /// Syncing the pending transactions
+    Transactions,
+    /// Requesting the balance of the addresses
+    Balances,
+    /// Requesting the output ids of the addresses with balance
+    OutputIds,
+    /// Requesting the outputs
+    Outputs,
+}
+
+/// Tracks the progress of a syncing and emits it as `SyncProgress` event
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SyncProgress {
+    start: Instant,
+}
+
+impl SyncProgress {
+    pub(crate) fn new(start: Instant) -> Self {
+        Self { start }
+    }
+
+    /// Report that `done` of `total` items of a phase are synced
+    pub(crate) async fn update(&self, account_handle: &AccountHandle, phase: SyncPhase, done: usize, total: usize) {
+        log::debug!(
+            "[SYNC] {:?} {}/{} after {:.2?}",
+            phase,
+            done,
+            total,
+            self.start.elapsed()
+        );
+        #[cfg(feature = "events")]
+        {
+            let account_index = account_handle.read().await.index;
+            account_handle.event_emitter.lock().await.emit(
+                account_index,
+                WalletEvent::SyncProgress(SyncProgressEvent {
+                    phase,
+                    done,
+                    total,
+                    elapsed: self.start.elapsed().as_millis(),
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(all(test, feature = "events"))]
+mod tests {
+    use super::{SyncPhase, SyncProgress};
+    use crate::{
+        account::{handle::AccountHandle, test_utils::account},
+        account_manager::operations::address_book::AddressBook,
+        events::{
+            types::{WalletEvent, WalletEventType},
+            EventEmitter,
+        },
+    };
+
+    use tokio::sync::Mutex;
+
+    use std::{sync::Arc, time::Instant};
+
+    #[tokio::test]
+    async fn sync_progress_event() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut event_emitter = EventEmitter::new();
+        let received_events = events.clone();
+        event_emitter.on(vec![WalletEventType::SyncProgress], move |event| {
+            received_events.lock().unwrap().push(event.clone())
+        });
+        let mut account = account();
+        account.index = 1;
+        let account_handle = AccountHandle::new(
+            account,
+            Arc::new(AddressBook::default()),
+            Arc::new(Mutex::new(event_emitter)),
+        );
+
+        SyncProgress::new(Instant::now())
+            .update(&account_handle, SyncPhase::Outputs, 2, 5)
+            .await;
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].account_index, 1);
+        match &events[0].event {
+            WalletEvent::SyncProgress(event) => {
+                assert_eq!((event.phase, event.done, event.total), (SyncPhase::Outputs, 2, 5))
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
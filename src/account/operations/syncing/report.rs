@@ -0,0 +1,38 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use iota_client::bee_message::output::OutputId;
+use serde::{Deserialize, Serialize};
+
+/// Summary of the latest syncing of an account
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Requests for the balance of addresses
+    #[serde(rename = "balanceRequests")]
+    pub balance_requests: usize,
+    /// Requests for the output ids of addresses
+    #[serde(rename = "outputIdsRequests")]
+    pub output_ids_requests: usize,
+    /// Requests for outputs, only new outputs and known unspent outputs that aren't returned for their address anymore
+    /// are requested
+    #[serde(rename = "outputRequests")]
+    pub output_requests: usize,
+    /// Requests for the transactions of new outputs that weren't sent by the account
+    #[serde(rename = "transactionRequests")]
+    pub transaction_requests: usize,
+    /// Requests for message metadata and milestones to get the timestamps of outputs and transactions
+    #[serde(rename = "timestampRequests")]
+    pub timestamp_requests: usize,
+    /// Unspent outputs that were already known and didn't need to be requested
+    #[serde(rename = "skippedOutputs")]
+    pub skipped_outputs: usize,
+    /// Bech32 addresses whose balance or output ids couldn't be requested, their previous state is kept and they are
+    /// requested first in the next syncing
+    #[serde(rename = "failedAddresses")]
+    pub failed_addresses: Vec<String>,
+    /// Outputs that couldn't be requested, they are requested first in the next syncing
+    #[serde(rename = "failedOutputs")]
+    pub failed_outputs: Vec<OutputId>,
+    /// Time the syncing took in milliseconds
+    pub duration: u128,
+}
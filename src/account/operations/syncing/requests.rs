@@ -0,0 +1,75 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::constants::{SYNC_REQUEST_RETRIES, SYNC_REQUEST_RETRY_DELAY};
+
+use std::future::Future;
+
+/// Send a request to the node, requests which failed with a transient error are retried with an exponential backoff.
+/// Every attempt waits for the request limits of the client options.
+pub(crate) async fn request_with_retries<T, F, Fut>(request: F) -> crate::Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = crate::Result<T>>,
+{
+    let request_limiter = crate::client::get_request_limiter().await?;
+    let mut delay = SYNC_REQUEST_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let permit = request_limiter.acquire().await;
+        let result = request().await;
+        drop(permit);
+        match result {
+            Ok(result) => return Ok(result),
+            Err(e) if attempt < SYNC_REQUEST_RETRIES && is_transient(&e) => {
+                log::debug!("[SYNC] request failed, retrying in {:?}: {}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The request can succeed with another attempt, because it timed out, couldn't connect, got rate limited or the node
+/// had an internal error. Other errors, like a bad request or missing data, would fail again.
+pub(crate) fn is_transient(error: &crate::Error) -> bool {
+    match error {
+        crate::Error::ClientError(error) => match &**error {
+            iota_client::Error::ResponseError(status, _) => *status == 429 || *status >= 500,
+            iota_client::Error::ReqwestError(error) => error.is_timeout() || error.is_connect(),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The node doesn't have the requested data, for example because it got pruned
+pub(crate) fn is_not_found(error: &crate::Error) -> bool {
+    matches!(error, crate::Error::ClientError(error) if matches!(**error, iota_client::Error::ResponseError(404, _)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_not_found, is_transient};
+
+    // An error response of the node with the status code
+    fn response_error(status: u16) -> crate::Error {
+        iota_client::Error::ResponseError(status, String::new()).into()
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&response_error(429)));
+        assert!(is_transient(&response_error(500)));
+        assert!(is_transient(&response_error(503)));
+        // a pruned node won't return the data with another attempt
+        assert!(!is_transient(&response_error(404)));
+        assert!(!is_transient(&response_error(400)));
+        assert!(!is_transient(&crate::Error::SyncCancelled));
+
+        assert!(is_not_found(&response_error(404)));
+        assert!(!is_not_found(&response_error(500)));
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::{MILESTONE_INTERVAL, PARALLEL_REQUESTS_AMOUNT},
+    operations::syncing::{requests::request_with_retries, SyncReport},
+    types::TimestampSource,
+};
+
+use iota_client::bee_message::MessageId;
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Get the timestamps of the milestones which referenced the messages. Messages that got pruned or aren't referenced
+/// yet get the local time. If only the milestone got pruned, the timestamp is estimated from the latest milestone.
+pub(crate) async fn get_message_timestamps(
+    message_ids: HashSet<MessageId>,
+    report: &mut SyncReport,
+) -> crate::Result<HashMap<MessageId, (u128, TimestampSource)>> {
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis();
+    if message_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let client = crate::client::get_client().await?;
+
+    // milestone indexes from the message metadata
+    let mut milestone_indexes: HashMap<MessageId, u32> = HashMap::new();
+    for message_ids_chunk in message_ids
+        .iter()
+        .copied()
+        .collect::<Vec<MessageId>>()
+        .chunks(PARALLEL_REQUESTS_AMOUNT)
+        .map(|x: &[MessageId]| x.to_vec())
+    {
+        report.timestamp_requests += message_ids_chunk.len();
+        let mut tasks = Vec::new();
+        for message_id in message_ids_chunk {
+            let client = client.clone();
+            tasks.push(async move {
+                tokio::spawn(async move {
+                    let client = &client;
+                    let metadata =
+                        request_with_retries(|| async move { Ok(client.get_message().metadata(&message_id).await?) })
+                            .await;
+                    let milestone_index = match metadata {
+                        Ok(metadata) => metadata.referenced_by_milestone_index,
+                        Err(e) => {
+                            log::debug!("[SYNC] no metadata for {}: {}", message_id, e);
+                            None
+                        }
+                    };
+                    (message_id, milestone_index)
+                })
+                .await
+            });
+        }
+        for (message_id, milestone_index) in futures::future::try_join_all(tasks).await? {
+            if let Some(milestone_index) = milestone_index {
+                milestone_indexes.insert(message_id, milestone_index);
+            }
+        }
+    }
+
+    // timestamps of the milestones, in ms
+    let mut milestone_timestamps: HashMap<u32, u128> = HashMap::new();
+    for indexes_chunk in milestone_indexes
+        .values()
+        .copied()
+        .collect::<HashSet<u32>>()
+        .into_iter()
+        .collect::<Vec<u32>>()
+        .chunks(PARALLEL_REQUESTS_AMOUNT)
+        .map(|x: &[u32]| x.to_vec())
+    {
+        report.timestamp_requests += indexes_chunk.len();
+        let mut tasks = Vec::new();
+        for milestone_index in indexes_chunk {
+            let client = client.clone();
+            tasks.push(async move {
+                tokio::spawn(async move {
+                    let client = &client;
+                    let milestone =
+                        request_with_retries(|| async move { Ok(client.get_milestone(milestone_index).await?) }).await;
+                    let timestamp = match milestone {
+                        Ok(milestone) => Some(milestone.timestamp as u128 * 1000),
+                        Err(e) => {
+                            log::debug!("[SYNC] no milestone {}: {}", milestone_index, e);
+                            None
+                        }
+                    };
+                    (milestone_index, timestamp)
+                })
+                .await
+            });
+        }
+        for (milestone_index, timestamp) in futures::future::try_join_all(tasks).await? {
+            if let Some(timestamp) = timestamp {
+                milestone_timestamps.insert(milestone_index, timestamp);
+            }
+        }
+    }
+
+    // pruned milestones are estimated backwards from the latest milestone
+    let latest_milestone = if milestone_indexes
+        .values()
+        .any(|index| !milestone_timestamps.contains_key(index))
+    {
+        report.timestamp_requests += 1;
+        match request_with_retries(|| async { Ok(client.get_info().await?) }).await {
+            Ok(node_info) => Some((
+                node_info.nodeinfo.latest_milestone_index,
+                node_info.nodeinfo.latest_milestone_timestamp as u128 * 1000,
+            )),
+            Err(e) => {
+                log::debug!("[SYNC] no node info: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Ok(message_ids
+        .into_iter()
+        .map(|message_id| {
+            let timestamp = select_timestamp(
+                milestone_indexes.get(&message_id).copied(),
+                &milestone_timestamps,
+                latest_milestone,
+                time_now,
+            );
+            (message_id, timestamp)
+        })
+        .collect())
+}
+
+// Get the timestamp of the milestone that referenced a message, an estimation if the milestone got pruned or the local
+// time if the message isn't referenced or the latest milestone is unknown
+fn select_timestamp(
+    milestone_index: Option<u32>,
+    milestone_timestamps: &HashMap<u32, u128>,
+    latest_milestone: Option<(u32, u128)>,
+    time_now: u128,
+) -> (u128, TimestampSource) {
+    match milestone_index {
+        Some(milestone_index) => match (milestone_timestamps.get(&milestone_index), latest_milestone) {
+            (Some(timestamp), _) => (*timestamp, TimestampSource::Milestone),
+            (None, Some((latest_index, latest_timestamp))) => (
+                estimate_milestone_timestamp(milestone_index, latest_index, latest_timestamp),
+                TimestampSource::EstimatedMilestone,
+            ),
+            (None, None) => (time_now, TimestampSource::Local),
+        },
+        None => (time_now, TimestampSource::Local),
+    }
+}
+
+// Estimate the timestamp of a milestone with the average milestone interval
+fn estimate_milestone_timestamp(milestone_index: u32, latest_index: u32, latest_timestamp: u128) -> u128 {
+    latest_timestamp.saturating_sub(latest_index.saturating_sub(milestone_index) as u128 * MILESTONE_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_milestone_timestamp, select_timestamp};
+    use crate::account::{constants::MILESTONE_INTERVAL, types::TimestampSource};
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn milestone_timestamp_estimation() {
+        let latest_timestamp = 1_000 * MILESTONE_INTERVAL;
+        assert_eq!(
+            estimate_milestone_timestamp(100, 100, latest_timestamp),
+            latest_timestamp
+        );
+        assert_eq!(
+            estimate_milestone_timestamp(90, 100, latest_timestamp),
+            latest_timestamp - 10 * MILESTONE_INTERVAL
+        );
+        // a milestone after the latest one and an estimation before the unix epoch don't overflow
+        assert_eq!(
+            estimate_milestone_timestamp(110, 100, latest_timestamp),
+            latest_timestamp
+        );
+        assert_eq!(estimate_milestone_timestamp(0, 2_000, latest_timestamp), 0);
+    }
+
+    #[test]
+    fn timestamp_source_selection() {
+        let time_now = 5_000_000;
+        let milestone_timestamps: HashMap<u32, u128> = vec![(10, 1_000_000)].into_iter().collect();
+        let latest_milestone = Some((20, 2_000_000));
+
+        assert_eq!(
+            select_timestamp(Some(10), &milestone_timestamps, latest_milestone, time_now),
+            (1_000_000, TimestampSource::Milestone)
+        );
+        // the milestone got pruned
+        assert_eq!(
+            select_timestamp(Some(15), &milestone_timestamps, latest_milestone, time_now),
+            (2_000_000 - 5 * MILESTONE_INTERVAL, TimestampSource::EstimatedMilestone)
+        );
+        // the milestone got pruned and the node info isn't available
+        assert_eq!(
+            select_timestamp(Some(15), &milestone_timestamps, None, time_now),
+            (time_now, TimestampSource::Local)
+        );
+        // the message got pruned or isn't referenced
+        assert_eq!(
+            select_timestamp(None, &milestone_timestamps, latest_milestone, time_now),
+            (time_now, TimestampSource::Local)
+        );
+    }
+}
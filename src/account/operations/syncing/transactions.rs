@@ -3,18 +3,21 @@
 
 use crate::account::{
     handle::AccountHandle,
-    operations::transfer::submit_transaction::submit_transaction_payload,
-    types::{InclusionState, Transaction},
+    operations::{
+        syncing::{
+            requests::{is_not_found, request_with_retries},
+            timestamps::get_message_timestamps,
+            SyncReport,
+        },
+        transfer::submit_transaction::submit_transaction_payload,
+    },
+    types::{ConflictReason, InclusionState, TimestampSource, Transaction},
 };
 
 use iota_client::{
-    bee_message::{
-        input::{Input, UtxoInput},
-        output::OutputId,
-        payload::transaction::Essence,
-        MessageId,
-    },
+    bee_message::{input::UtxoInput, output::OutputId, payload::transaction::TransactionId, MessageId},
     bee_rest_api::types::dtos::LedgerInclusionStateDto,
+    Client,
 };
 
 use std::{
@@ -29,21 +32,30 @@ use std::{
 // retry(message_id, sync: false)
 // }.await?;
 
-/// Sync transactions and reattach them if unconfirmed. Returns the transaction with updated metadata and spent output
-/// ids that don't need to be locked anymore
+/// Sync transactions and reattach them if unconfirmed, `only` limits it to a single pending transaction. Returns the
+/// transaction with updated metadata, spent output ids that don't need to be locked anymore and unspent inputs of
+/// conflicting transactions that can be unlocked
 pub(crate) async fn sync_transactions(
     account_handle: &AccountHandle,
-) -> crate::Result<(Vec<Transaction>, Vec<OutputId>)> {
+    only: Option<TransactionId>,
+    report: &mut SyncReport,
+) -> crate::Result<(Vec<Transaction>, Vec<OutputId>, Vec<OutputId>)> {
     log::debug!("[SYNC] sync pending transactions");
     let account = account_handle.read().await;
     let client = crate::client::get_client().await?;
     let network_id = client.get_network_id().await?;
+    let reattach_policy = account.account_options.reattach_policy;
 
     let mut updated_transactions = Vec::new();
     let mut spent_output_ids = Vec::new();
+    let mut unspent_input_ids = Vec::new();
     let mut transactions_to_reattach = Vec::new();
 
-    for transaction_id in &account.pending_transactions {
+    for transaction_id in account
+        .pending_transactions
+        .iter()
+        .filter(|transaction_id| only.map_or(true, |only| only == **transaction_id))
+    {
         let transaction = account
             .transactions
             .get(transaction_id)
@@ -53,9 +65,11 @@ pub(crate) async fn sync_transactions(
         // only check transaction from the network we're connected to
         if transaction.network_id == network_id {
             // use first output of the transaction to check if it got confirmed
-            if let Ok(output_response) = client
-                .get_output(&UtxoInput::from(OutputId::new(transaction.payload.id(), 0)?))
-                .await
+            let output_id = UtxoInput::from(OutputId::new(transaction.payload.id(), 0)?);
+            let client = &client;
+            let output_id = &output_id;
+            if let Ok(output_response) =
+                request_with_retries(|| async move { Ok(client.get_output(output_id).await?) }).await
             {
                 updated_transaction_and_outputs(
                     transaction,
@@ -65,7 +79,17 @@ pub(crate) async fn sync_transactions(
                     &mut spent_output_ids,
                 );
             } else if let Some(message_id) = transaction.message_id {
-                let metadata = client.get_message().metadata(&message_id).await?;
+                let metadata =
+                    match request_with_retries(|| async move { Ok(client.get_message().metadata(&message_id).await?) })
+                        .await
+                    {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            // the transaction stays pending and gets synced again the next time
+                            log::debug!("[SYNC] failed to get the metadata of {}: {}", message_id, e);
+                            continue;
+                        }
+                    };
                 if let Some(inclusion_state) = metadata.ledger_inclusion_state {
                     match inclusion_state {
                         LedgerInclusionStateDto::Included => {
@@ -80,7 +104,12 @@ pub(crate) async fn sync_transactions(
                         LedgerInclusionStateDto::Conflicting => {
                             // try to get the included message, because maybe only this attachment is conflicting
                             // because it got confirmed in another message
-                            if let Ok(included_message) = client.get_included_message(&transaction.payload.id()).await {
+                            let transaction_id = transaction.payload.id();
+                            if let Ok(included_message) = request_with_retries(|| async move {
+                                Ok(client.get_included_message(&transaction_id).await?)
+                            })
+                            .await
+                            {
                                 updated_transaction_and_outputs(
                                     transaction,
                                     included_message.id().0,
@@ -89,15 +118,32 @@ pub(crate) async fn sync_transactions(
                                     &mut spent_output_ids,
                                 );
                             } else {
-                                // a part of the outputs could still be unspent, but when we set it as spent we will get
-                                // it as unspent later during syncing again
-                                updated_transaction_and_outputs(
-                                    transaction,
-                                    MessageId::from_str(&metadata.message_id)?,
-                                    InclusionState::Conflicting,
-                                    &mut updated_transactions,
-                                    &mut spent_output_ids,
+                                // only inputs that another transaction spent are spent, the other ones get unlocked
+                                let (spent_inputs, unspent_inputs) = match get_inputs_spent_status(client, &transaction)
+                                    .await
+                                {
+                                    Ok(inputs) => inputs,
+                                    Err(e) => {
+                                        log::debug!("[SYNC] failed to check the inputs of {}: {}", transaction_id, e);
+                                        continue;
+                                    }
+                                };
+                                let mut transaction = transaction;
+                                transaction
+                                    .message_id
+                                    .replace(MessageId::from_str(&metadata.message_id)?);
+                                transaction.inclusion_state = InclusionState::Conflicting;
+                                transaction.conflict_reason =
+                                    metadata.conflict_reason.and_then(ConflictReason::from_code);
+                                log::debug!(
+                                    "[SYNC] transaction {} is conflicting: {:?}, unspent inputs: {:?}",
+                                    transaction_id,
+                                    transaction.conflict_reason,
+                                    unspent_inputs
                                 );
+                                spent_output_ids.extend(spent_inputs);
+                                unspent_input_ids.extend(unspent_inputs);
+                                updated_transactions.push(transaction);
                             }
                         }
                         LedgerInclusionStateDto::NoTransaction => {}
@@ -107,26 +153,113 @@ pub(crate) async fn sync_transactions(
                         .duration_since(UNIX_EPOCH)
                         .expect("Time went backwards")
                         .as_millis();
-                    // Reattach if older than 30 seconds
-                    if transaction.timestamp + 30000 < time_now {
+                    // Reattach depending on the reattach policy, but not if it got cancelled or replaced, then only the
+                    // replacement should get confirmed. Transactions with a running retry task get reattached by it.
+                    if reattach_policy.should_reattach(&transaction, time_now)
+                        && transaction.replaced_by.is_none()
+                        && !account_handle.retry_tasks.contains(&transaction.payload.id())
+                    {
                         transactions_to_reattach.push(transaction);
                     }
                 }
-            } else {
+            } else if transaction.replaced_by.is_none() {
                 // transaction wasn't submitted yet, so we have to send it again
                 transactions_to_reattach.push(transaction);
             }
         }
     }
+
+    // cancelled or replaced transactions spend the same inputs as their replacement, so if one of them got confirmed,
+    // the other one is conflicting
+    let conflicting_transaction_ids: Vec<TransactionId> = updated_transactions
+        .iter()
+        .filter(|transaction| transaction.inclusion_state == InclusionState::Confirmed)
+        .flat_map(|transaction| transaction.replaces.into_iter().chain(transaction.replaced_by))
+        .collect();
+    for transaction_id in conflicting_transaction_ids {
+        if !account.pending_transactions.contains(&transaction_id)
+            || updated_transactions.iter().any(|t| t.payload.id() == transaction_id)
+        {
+            continue;
+        }
+        transactions_to_reattach.retain(|t| t.payload.id() != transaction_id);
+        if let Some(transaction) = account.transactions.get(&transaction_id) {
+            log::debug!(
+                "[SYNC] transaction {} conflicts with its confirmed replacement",
+                transaction_id
+            );
+            let mut transaction = transaction.clone();
+            // the inputs are already marked as spent from the confirmed transaction
+            transaction.inclusion_state = InclusionState::Conflicting;
+            updated_transactions.push(transaction);
+        }
+    }
     drop(account);
+
+    // confirmed transactions are dated with the milestone that confirmed them, like incoming transactions
+    let timestamps = get_message_timestamps(
+        updated_transactions
+            .iter()
+            .filter(|t| t.inclusion_state == InclusionState::Confirmed)
+            .filter_map(|t| t.message_id)
+            .collect(),
+        report,
+    )
+    .await?;
+    for transaction in updated_transactions.iter_mut() {
+        if let Some((timestamp, timestamp_source)) = transaction.message_id.and_then(|id| timestamps.get(&id)) {
+            // keep the creation time if the message got pruned
+            if *timestamp_source != TimestampSource::Local {
+                transaction.timestamp = *timestamp;
+                transaction.timestamp_source = *timestamp_source;
+            }
+        }
+    }
+
     for mut transaction in transactions_to_reattach {
         log::debug!("[SYNC] reattach transaction");
-        let reattached_msg = submit_transaction_payload(account_handle, transaction.payload.clone()).await?;
+        let reattached_msg = match submit_transaction_payload(account_handle, transaction.payload.clone()).await {
+            Ok(message_id) => message_id,
+            Err(e) => {
+                log::debug!("[SYNC] failed to reattach {}: {}", transaction.payload.id(), e);
+                continue;
+            }
+        };
+        // transactions that weren't submitted before don't count as reattachment
+        if transaction.message_id.is_some() {
+            transaction.reattachments += 1;
+            transaction.last_reattachment.replace(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_millis(),
+            );
+        }
         transaction.message_id.replace(reattached_msg);
         updated_transactions.push(transaction);
     }
 
-    Ok((updated_transactions, spent_output_ids))
+    Ok((updated_transactions, spent_output_ids, unspent_input_ids))
+}
+
+// Check the inputs of a transaction on the node, returns the spent and the unspent input ids. Inputs the node doesn't
+// know are returned as spent, because they can't be used anymore.
+async fn get_inputs_spent_status(
+    client: &Client,
+    transaction: &Transaction,
+) -> crate::Result<(Vec<OutputId>, Vec<OutputId>)> {
+    let mut spent_inputs = Vec::new();
+    let mut unspent_inputs = Vec::new();
+    for input_id in transaction.input_ids() {
+        let input = &UtxoInput::from(input_id);
+        match request_with_retries(|| async move { Ok(client.get_output(input).await?) }).await {
+            Ok(output) if !output.is_spent => unspent_inputs.push(input_id),
+            Ok(_) => spent_inputs.push(input_id),
+            Err(e) if is_not_found(&e) => spent_inputs.push(input_id),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((spent_inputs, unspent_inputs))
 }
 
 fn updated_transaction_and_outputs(
@@ -139,11 +272,6 @@ fn updated_transaction_and_outputs(
     transaction.message_id.replace(message_id);
     transaction.inclusion_state = inclusion_state;
     // get spent inputs
-    let Essence::Regular(essence) = transaction.payload.essence();
-    for input in essence.inputs() {
-        if let Input::Utxo(input) = input {
-            spent_output_ids.push(*input.output_id());
-        }
-    }
+    spent_output_ids.extend(transaction.input_ids());
     updated_transactions.push(transaction);
 }
@@ -0,0 +1,177 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    handle::AccountHandle,
+    operations::transfer::{Remainder, TransferOutput},
+    types::OutputData,
+};
+
+use iota_client::bee_message::payload::{indexation::IndexationPayload, transaction::Essence, Payload};
+
+use std::{
+    fmt::{Debug, Formatter, Result},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+};
+
+/// A transaction right before it gets signed, passed to the approval hook
+#[derive(Debug, Clone)]
+pub struct PreparedTransaction {
+    /// The transaction essence that will be signed
+    pub essence: Essence,
+    /// The outputs that are used as inputs
+    pub inputs: Vec<OutputData>,
+    /// The outputs of the transfer, without the remainder
+    pub outputs: Vec<TransferOutput>,
+    /// The bech32 address that receives the remainder
+    pub remainder_address: Option<String>,
+    /// The remainder amount
+    pub remainder_amount: u64,
+    /// The indexation payload of the transaction
+    pub indexation: Option<IndexationPayload>,
+}
+
+/// The decision of the approval hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// Sign and send the transaction
+    Approve,
+    /// Don't sign the transaction, with the reason for the rejection
+    Reject(String),
+}
+
+type ApprovalFuture = Pin<Box<dyn Future<Output = ApprovalDecision> + Send>>;
+
+/// Async callback which approves or rejects transactions before they get signed
+#[derive(Clone)]
+pub(crate) struct ApprovalHook(pub(crate) Arc<dyn Fn(PreparedTransaction) -> ApprovalFuture + Send + Sync>);
+
+impl ApprovalHook {
+    pub(crate) fn new<F, Fut>(hook: F) -> Self
+    where
+        F: Fn(PreparedTransaction) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ApprovalDecision> + Send + 'static,
+    {
+        Self(Arc::new(move |prepared_transaction| {
+            Box::pin(hook(prepared_transaction))
+        }))
+    }
+}
+
+impl Debug for ApprovalHook {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "ApprovalHook")
+    }
+}
+
+/// Passes the prepared transaction to the approval hook of the account, if one is set. A rejection returns
+/// [`crate::Error::TransactionRejected`].
+pub(crate) async fn approve_transaction(
+    account_handle: &AccountHandle,
+    essence: &Essence,
+    inputs: &[OutputData],
+    outputs: &[TransferOutput],
+    remainder: &Option<Remainder>,
+) -> crate::Result<()> {
+    // clone the hook, so the lock isn't hold while waiting for the decision
+    let approval_hook = match &*account_handle.approval_hook.lock().await {
+        Some(approval_hook) => approval_hook.clone(),
+        None => return Ok(()),
+    };
+    log::debug!("[TRANSFER] waiting for approval");
+    let prepared_transaction = prepare_transaction(essence, inputs, outputs, remainder);
+    match (approval_hook.0)(prepared_transaction).await {
+        ApprovalDecision::Approve => Ok(()),
+        ApprovalDecision::Reject(reason) => {
+            log::debug!("[TRANSFER] transaction rejected: {}", reason);
+            Err(crate::Error::TransactionRejected(reason))
+        }
+    }
+}
+
+// Collect the data of a transaction for the approval hook
+fn prepare_transaction(
+    essence: &Essence,
+    inputs: &[OutputData],
+    outputs: &[TransferOutput],
+    remainder: &Option<Remainder>,
+) -> PreparedTransaction {
+    let Essence::Regular(regular_essence) = essence;
+    let indexation = match regular_essence.payload() {
+        Some(Payload::Indexation(indexation)) => Some((**indexation).clone()),
+        _ => None,
+    };
+    PreparedTransaction {
+        essence: essence.clone(),
+        inputs: inputs.to_vec(),
+        outputs: outputs.to_vec(),
+        remainder_address: remainder.as_ref().map(|r| r.address.address.to_bech32()),
+        remainder_amount: remainder.as_ref().map(|r| r.amount).unwrap_or_default(),
+        indexation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prepare_transaction, ApprovalDecision, ApprovalHook, PreparedTransaction};
+    use crate::account::{
+        operations::transfer::Remainder,
+        test_utils::{account_address, address, output, output_id, transaction},
+    };
+
+    use iota_client::bee_message::{
+        input::{Input, UtxoInput},
+        output::SignatureLockedSingleOutput,
+        payload::{
+            indexation::IndexationPayload,
+            transaction::{Essence, RegularEssence},
+            Payload,
+        },
+    };
+
+    #[tokio::test]
+    async fn approval_hook() {
+        let essence = RegularEssence::builder()
+            .with_inputs(vec![Input::Utxo(UtxoInput::from(output_id(1, 0)))])
+            .with_outputs(vec![SignatureLockedSingleOutput::new(address(1), 1_000_000)
+                .unwrap()
+                .into()])
+            .with_payload(Payload::Indexation(Box::new(
+                IndexationPayload::new(b"index", b"data").unwrap(),
+            )))
+            .finish()
+            .unwrap();
+        let prepared_transaction = prepare_transaction(&Essence::Regular(essence), &[], &[], &None);
+        assert_eq!(prepared_transaction.indexation.unwrap().data(), &b"data"[..]);
+        assert!(prepared_transaction.remainder_address.is_none());
+        assert_eq!(prepared_transaction.remainder_amount, 0);
+
+        let transaction = transaction(&[output_id(1, 0)], &[(address(1), 1_000_000)]);
+        let remainder = Some(Remainder {
+            address: account_address(2, 0, true),
+            amount: 2_000_000,
+        });
+        let inputs = vec![output(output_id(1, 0), address(3), 3_000_000)];
+        let prepared_transaction = prepare_transaction(transaction.payload.essence(), &inputs, &[], &remainder);
+        assert_eq!(
+            prepared_transaction.remainder_address,
+            Some(address(2).to_bech32("atoi"))
+        );
+        assert_eq!(prepared_transaction.remainder_amount, 2_000_000);
+        assert_eq!(prepared_transaction.inputs.len(), 1);
+
+        // the hook decides with the prepared transaction
+        let hook = ApprovalHook::new(|prepared_transaction: PreparedTransaction| async move {
+            match prepared_transaction.remainder_amount {
+                0 => ApprovalDecision::Approve,
+                _ => ApprovalDecision::Reject("no remainder allowed".to_string()),
+            }
+        });
+        assert_eq!(
+            (hook.0)(prepared_transaction).await,
+            ApprovalDecision::Reject("no remainder allowed".to_string())
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    handle::AccountHandle,
+    operations::transfer::{TransferOutput, TransferResult},
+    Account,
+};
+
+use iota_client::bee_message::payload::transaction::TransactionId;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// The transaction that got created for an idempotency key, with the outputs of the transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    /// The outputs of the transfer, a transfer with the same key needs to have the same outputs
+    pub outputs: Vec<TransferOutput>,
+    /// The transaction that got created for the key
+    #[serde(rename = "transactionId")]
+    pub transaction_id: TransactionId,
+}
+
+/// Idempotency keys of transfers which are in progress, they aren't stored
+#[derive(Debug, Default)]
+pub(crate) struct ReservedIdempotencyKeys(Mutex<HashSet<String>>);
+
+impl ReservedIdempotencyKeys {
+    // a poisoned lock still has the keys of transfers in progress, a transfer could be sent twice without them
+    fn keys(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A reserved idempotency key with the outputs of its transfer. The key is released when the reservation is dropped,
+/// also if the transfer gets cancelled by dropping its future.
+pub(crate) struct IdempotencyKeyReservation {
+    reserved_idempotency_keys: Arc<ReservedIdempotencyKeys>,
+    idempotency_key: String,
+    outputs: Vec<TransferOutput>,
+}
+
+impl IdempotencyKeyReservation {
+    /// Records the transaction for the idempotency key, it needs to be called while the transaction gets stored, so
+    /// both are saved together
+    pub(crate) fn record(&self, account: &mut Account, transaction_id: TransactionId) {
+        account.idempotency_keys.insert(
+            self.idempotency_key.clone(),
+            IdempotencyRecord {
+                outputs: self.outputs.clone(),
+                transaction_id,
+            },
+        );
+    }
+}
+
+impl Drop for IdempotencyKeyReservation {
+    fn drop(&mut self) {
+        self.reserved_idempotency_keys.keys().remove(&self.idempotency_key);
+    }
+}
+
+/// The state of an idempotency key
+pub(crate) enum IdempotencyKey {
+    /// A transfer with the key was already sent
+    Sent(TransferResult),
+    /// The key is reserved for a new transfer
+    Reserved(IdempotencyKeyReservation),
+}
+
+/// Checks if a transfer with the idempotency key was already sent and returns its result in that case. Otherwise the
+/// key gets reserved until the returned reservation is dropped, so a retry running at the same time can't send another
+/// transaction.
+pub(crate) async fn reserve_idempotency_key(
+    account_handle: &AccountHandle,
+    idempotency_key: &str,
+    outputs: &[TransferOutput],
+) -> crate::Result<IdempotencyKey> {
+    // the transaction for the key is recorded with the write lock, so it can't be stored in between
+    let account = account_handle.write().await;
+    if let Some(record) = account.idempotency_keys.get(idempotency_key) {
+        if record.outputs != outputs {
+            return Err(crate::Error::IdempotencyKeyConflict(format!(
+                "{} was used for a transfer with other outputs",
+                idempotency_key
+            )));
+        }
+        log::debug!(
+            "[TRANSFER] {} was already used for transaction {}",
+            idempotency_key,
+            record.transaction_id
+        );
+        let transaction_id = record.transaction_id;
+        let message_id = account
+            .transactions
+            .get(&transaction_id)
+            .and_then(|transaction| transaction.message_id);
+        return Ok(IdempotencyKey::Sent(TransferResult {
+            transaction_id,
+            message_id,
+            own_address_recipients: Vec::new(),
+        }));
+    }
+    if !account_handle
+        .reserved_idempotency_keys
+        .keys()
+        .insert(idempotency_key.to_string())
+    {
+        return Err(crate::Error::IdempotencyKeyConflict(format!(
+            "a transfer with {} is in progress",
+            idempotency_key
+        )));
+    }
+    Ok(IdempotencyKey::Reserved(IdempotencyKeyReservation {
+        reserved_idempotency_keys: account_handle.reserved_idempotency_keys.clone(),
+        idempotency_key: idempotency_key.to_string(),
+        outputs: outputs.to_vec(),
+    }))
+}
@@ -4,6 +4,7 @@
 use crate::account::{
     constants::MIN_DUST_ALLOWANCE_VALUE,
     handle::AccountHandle,
+    operations::output_locks::{lock_outputs, save_output_locks},
     types::{OutputData, OutputKind},
 };
 #[cfg(feature = "events")]
@@ -55,9 +56,11 @@ pub(crate) async fn select_inputs(
             )));
         }
         // lock outputs so they don't get used by another transaction
-        for output in &inputs {
-            account.locked_outputs.insert(output.output_id);
-        }
+        lock_outputs(
+            &mut account,
+            &inputs.iter().map(|o| o.output_id).collect::<Vec<OutputId>>(),
+        );
+        save_output_locks(&account).await?;
         return Ok(inputs);
     }
 
@@ -67,8 +70,12 @@ pub(crate) async fn select_inputs(
     let mut signature_locked_outputs = Vec::new();
     let mut dust_allowance_outputs = Vec::new();
     for (output_id, output) in account.unspent_outputs.iter() {
-        // check if not in pending transaction (locked_outputs) and if from the correct network
-        if !output.is_spent && !account.locked_outputs.contains(output_id) && output.network_id == network_id {
+        // check if not in pending transaction (locked_outputs), not frozen and if from the correct network
+        if !output.is_spent
+            && !account.locked_outputs.contains(output_id)
+            && !account.frozen_outputs.contains(output_id)
+            && output.network_id == network_id
+        {
             match output.kind {
                 OutputKind::SignatureLockedSingle => signature_locked_outputs.push(output),
                 OutputKind::SignatureLockedDustAllowance => dust_allowance_outputs.push(output),
@@ -126,12 +133,10 @@ pub(crate) async fn select_inputs(
     }
 
     // lock outputs so they don't get used by another transaction
-    for output in &selected_outputs {
-        // log::debug!(
-        //     "[TRANSFER] select_inputs: lock {}",
-        //     output.output_id,
-        // );
-        account.locked_outputs.insert(output.output_id);
-    }
+    lock_outputs(
+        &mut account,
+        &selected_outputs.iter().map(|o| o.output_id).collect::<Vec<OutputId>>(),
+    );
+    save_output_locks(&account).await?;
     Ok(selected_outputs)
 }
@@ -3,18 +3,31 @@
 
 // transfer or transaction?
 
+pub(crate) mod approval;
 mod create_transaction;
+pub(crate) mod idempotency;
 mod input_selection;
 mod options;
+pub(crate) mod replace_transaction;
 mod sign_transaction;
 pub(crate) mod submit_transaction;
+mod validation;
 
-use crate::account::{
-    handle::AccountHandle,
-    types::{address::AccountAddress, InclusionState, OutputData, Transaction},
+use crate::{
+    account::{
+        handle::AccountHandle,
+        operations::{
+            output_locks::{save_output_locks, set_lock_transaction, unlock_outputs},
+            spending_policy::check_spending_policy,
+        },
+        types::{address::AccountAddress, Annotation, InclusionState, OutputData, TimestampSource, Transaction},
+    },
+    account_manager::operations::address_book,
 };
+use idempotency::{IdempotencyKey, IdempotencyKeyReservation};
 use input_selection::select_inputs;
 
+pub use approval::{ApprovalDecision, PreparedTransaction};
 use iota_client::bee_message::{
     constants::{INPUT_OUTPUT_COUNT_MAX, INPUT_OUTPUT_COUNT_RANGE},
     output::OutputId,
@@ -22,13 +35,23 @@ use iota_client::bee_message::{
     MessageId,
 };
 pub use options::{RemainderValueStrategy, TransferOptions, TransferOutput};
+use serde::{Deserialize, Serialize};
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// The result of a transfer, message_id is an option because submitting the transaction could fail
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferResult {
+    #[serde(rename = "transactionId")]
     pub transaction_id: TransactionId,
+    #[serde(rename = "messageId")]
     pub message_id: Option<MessageId>,
+    /// Recipients which are addresses of the account, not set if `skip_own_address_warning` is set in the options
+    #[serde(rename = "ownAddressRecipients", default)]
+    pub own_address_recipients: Vec<String>,
 }
 
 // Data for signing metadata (used for ledger signer)
@@ -45,6 +68,28 @@ pub async fn send_transfer(
     options: Option<TransferOptions>,
 ) -> crate::Result<TransferResult> {
     log::debug!("[TRANSFER] send_transfer");
+    // a transfer with an idempotency key is only sent once, retries return the result of the first transfer
+    let idempotency_key = match options.as_ref().and_then(|options| options.idempotency_key.clone()) {
+        Some(idempotency_key) => idempotency_key,
+        None => return send_new_transfer(account_handle, outputs, options, None).await,
+    };
+    let reservation = match idempotency::reserve_idempotency_key(account_handle, &idempotency_key, &outputs).await? {
+        IdempotencyKey::Sent(transfer_result) => return Ok(transfer_result),
+        IdempotencyKey::Reserved(reservation) => reservation,
+    };
+    // the key is released when the reservation is dropped, after the transaction got stored for it
+    send_new_transfer(account_handle, outputs, options, Some(&reservation)).await
+}
+
+// Send a transfer without checking the idempotency key
+async fn send_new_transfer(
+    account_handle: &AccountHandle,
+    mut outputs: Vec<TransferOutput>,
+    options: Option<TransferOptions>,
+    idempotency_key: Option<&IdempotencyKeyReservation>,
+) -> crate::Result<TransferResult> {
+    let bech32_hrp = crate::client::get_client().await?.get_bech32_hrp().await?;
+    address_book::resolve_contacts(&account_handle.address_book, &mut outputs, &bech32_hrp).await?;
     let amount = outputs.iter().map(|x| x.amount).sum();
     if amount == 0 {
         return Err(crate::Error::EmptyOutputAmount);
@@ -66,10 +111,19 @@ pub async fn send_transfer(
             None
         }
     };
+    let own_address_recipients = validation::validate_outputs(account_handle, &outputs, &options).await?;
+    // kept until the transaction is stored, so the amount counts for the rolling limits of concurrent transfers
+    let _spending_reservation = check_spending_policy(account_handle, &outputs, None).await?;
+    let annotation = options
+        .as_ref()
+        .and_then(|options| options.annotation.clone())
+        .unwrap_or_default();
     let inputs = select_inputs(account_handle, amount, custom_inputs).await?;
     // can we unlock the outputs in a better way if the transaction creation fails?
     let (essence, inputs_for_signing, remainder) =
-        match create_transaction::create_transaction(account_handle, inputs.clone(), outputs.clone(), options).await {
+        match create_transaction::create_transaction(account_handle, inputs.clone(), outputs.clone(), options.clone())
+            .await
+        {
             Ok(res) => res,
             Err(err) => {
                 // unlock outputs so they are available for a new transaction
@@ -77,6 +131,17 @@ pub async fn send_transfer(
                 return Err(err);
             }
         };
+    // the remainder is only known after the input selection
+    if let Err(err) = validation::validate_dust_allowance(&outputs, &remainder, &options).await {
+        // unlock outputs so they are available for a new transaction
+        unlock_inputs(account_handle, inputs).await?;
+        return Err(err);
+    }
+    if let Err(err) = approval::approve_transaction(account_handle, &essence, &inputs, &outputs, &remainder).await {
+        // unlock outputs so they are available for a new transaction
+        unlock_inputs(account_handle, inputs).await?;
+        return Err(err);
+    }
     let transaction_payload =
         match sign_transaction::sign_tx_essence(account_handle, essence, inputs_for_signing, remainder).await {
             Ok(res) => res,
@@ -87,6 +152,22 @@ pub async fn send_transfer(
             }
         };
 
+    let mut transfer_result =
+        submit_and_store_transaction(account_handle, transaction_payload, None, annotation, idempotency_key).await?;
+    transfer_result.own_address_recipients = own_address_recipients;
+    Ok(transfer_result)
+}
+
+/// Submits a signed transaction payload and stores it as pending transaction in the account. `replaces` links the
+/// transaction to a pending one with the same inputs, which got cancelled or replaced by it. The transaction is
+/// recorded for the reserved idempotency key in the same save.
+pub(crate) async fn submit_and_store_transaction(
+    account_handle: &AccountHandle,
+    transaction_payload: TransactionPayload,
+    replaces: Option<TransactionId>,
+    annotation: Annotation,
+    idempotency_key: Option<&IdempotencyKeyReservation>,
+) -> crate::Result<TransferResult> {
     let message_id =
         match submit_transaction::submit_transaction_payload(account_handle, transaction_payload.clone()).await {
             Ok(message_id) => Some(message_id),
@@ -97,23 +178,42 @@ pub async fn send_transfer(
     let client = crate::client::get_client().await?;
     let network_id = client.get_network_id().await?;
     let transaction_id = transaction_payload.id();
+    let transaction = Transaction {
+        payload: transaction_payload,
+        message_id,
+        network_id,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_millis(),
+        timestamp_source: TimestampSource::Local,
+        inclusion_state: InclusionState::Pending,
+        incoming: false,
+        internal: false,
+        replaces,
+        replaced_by: None,
+        reattachments: 0,
+        last_reattachment: None,
+        annotation,
+        senders: Vec::new(),
+        received_amount: None,
+        conflict_reason: None,
+        contacts: HashMap::new(),
+    };
+    let input_ids = transaction.input_ids();
     let mut account = account_handle.write().await;
-    account.transactions.insert(
-        transaction_id,
-        Transaction {
-            payload: transaction_payload,
-            message_id,
-            network_id,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_millis(),
-            inclusion_state: InclusionState::Pending,
-            incoming: false,
-            internal: false,
-        },
-    );
+    account.transactions.insert(transaction_id, transaction);
     account.pending_transactions.insert(transaction_id);
+    if let Some(reservation) = idempotency_key {
+        reservation.record(&mut account, transaction_id);
+    }
+    // the locks of the inputs now belong to this transaction
+    set_lock_transaction(&mut account, &input_ids, transaction_id);
+    if let Some(replaced_transaction_id) = replaces {
+        replace_transaction::link_replacement(&mut account, replaced_transaction_id, transaction_id);
+        // the replaced transaction can't get confirmed anymore if the replacement does, so it isn't retried anymore
+        account_handle.retry_tasks.abort(&replaced_transaction_id);
+    }
     #[cfg(feature = "storage")]
     log::debug!("[TRANSFER] storing account {}", account.index());
     crate::storage::manager::get()
@@ -122,17 +222,20 @@ pub async fn send_transfer(
         .await
         .save_account(&account)
         .await?;
+    save_output_locks(&account).await?;
     Ok(TransferResult {
         transaction_id,
         message_id,
+        own_address_recipients: Vec::new(),
     })
 }
 
 // unlock outputs
 async fn unlock_inputs(account_handle: &AccountHandle, inputs: Vec<OutputData>) -> crate::Result<()> {
     let mut account = account_handle.write().await;
-    for output in &inputs {
-        account.locked_outputs.remove(&output.output_id);
-    }
-    Ok(())
+    unlock_outputs(
+        &mut account,
+        &inputs.iter().map(|o| o.output_id).collect::<Vec<OutputId>>(),
+    );
+    save_output_locks(&account).await
 }
@@ -1,7 +1,7 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::account::types::{address::AccountAddress, OutputKind};
+use crate::account::types::{address::AccountAddress, Annotation, OutputKind};
 
 use iota_client::bee_message::{output::OutputId, payload::indexation::IndexationPayload};
 use serde::{Deserialize, Serialize};
@@ -18,12 +18,22 @@ pub struct TransferOptions {
     pub skip_sync: bool,
     #[serde(rename = "customInputs", default)]
     pub custom_inputs: Option<Vec<OutputId>>,
+    /// Note, label and tags for the transaction, they're only stored locally
+    #[serde(default)]
+    pub annotation: Option<Annotation>,
+    /// Key to identify a transfer, a transfer with a key that was already used returns the result of the first
+    /// transfer instead of sending a new transaction
+    #[serde(rename = "idempotencyKey", default)]
+    pub idempotency_key: Option<String>,
+    /// Don't warn if a recipient is an address of the account
+    #[serde(rename = "skipOwnAddressWarning", default)]
+    pub skip_own_address_warning: bool,
 }
 
 // clearer to have it here in transfer.rs or also mvoe it into the types folder?
 
 /// An output for a value transfer
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TransferOutput {
     pub address: String,
     pub amount: u64,
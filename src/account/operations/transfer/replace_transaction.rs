@@ -0,0 +1,402 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    account::{
+        handle::AccountHandle,
+        operations::{
+            address_generation::AddressGenerationOptions,
+            spending_policy::check_spending_policy,
+            transfer::{
+                approval::approve_transaction,
+                create_transaction::create_transaction,
+                send_transfer,
+                sign_transaction::sign_tx_essence,
+                submit_and_store_transaction,
+                validation::{validate_dust_allowance, validate_outputs},
+                TransferOptions, TransferOutput, TransferResult,
+            },
+        },
+        types::{address::AccountAddress, OutputData, OutputKind},
+        Account,
+    },
+    account_manager::operations::address_book,
+};
+
+use iota_client::bee_message::{
+    address::Address,
+    constants::{INPUT_OUTPUT_COUNT_MAX, INPUT_OUTPUT_COUNT_RANGE},
+    input::UtxoInput,
+    output::{Output, OutputId},
+    payload::{
+        transaction::{Essence, TransactionId},
+        Payload,
+    },
+};
+
+use std::collections::HashSet;
+
+/// Cancels a pending transaction by sending all of its inputs to an unused internal address of the account, which the
+/// transaction doesn't send to. Both transactions spend the same inputs, so at most one of them can get confirmed.
+pub(crate) async fn cancel_transaction(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+) -> crate::Result<TransferResult> {
+    log::debug!("[TRANSFER] cancel_transaction {}", transaction_id);
+    let inputs = get_replaceable_inputs(account_handle, &transaction_id).await?;
+    let amount = inputs.iter().map(|i| i.amount).sum();
+    // the checks before sending can reject the cancellation, so an unused address is reused instead of generating a
+    // new one each time
+    let unused_address = get_unused_internal_address(&account_handle.read().await, &transaction_id);
+    let address = match unused_address {
+        Some(address) => address,
+        None => account_handle
+            .generate_addresses(
+                1,
+                Some(AddressGenerationOptions {
+                    internal: true,
+                    ..Default::default()
+                }),
+            )
+            .await?
+            .first()
+            .expect("Didn't generated an address")
+            .clone(),
+    };
+    let outputs = vec![TransferOutput {
+        address: address.address.to_bech32(),
+        amount,
+        output_kind: None,
+    }];
+    send_replacement(account_handle, transaction_id, inputs, outputs).await
+}
+
+/// Replaces a pending transaction with a transaction to other outputs, which spends the same inputs. The remainder
+/// is sent to a change address.
+pub(crate) async fn replace_transaction(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+    mut outputs: Vec<TransferOutput>,
+) -> crate::Result<TransferResult> {
+    log::debug!("[TRANSFER] replace_transaction {}", transaction_id);
+    let bech32_hrp = crate::client::get_client().await?.get_bech32_hrp().await?;
+    address_book::resolve_contacts(&account_handle.address_book, &mut outputs, &bech32_hrp).await?;
+    let amount: u64 = outputs.iter().map(|x| x.amount).sum();
+    if amount == 0 {
+        return Err(crate::Error::EmptyOutputAmount);
+    };
+    if !INPUT_OUTPUT_COUNT_RANGE.contains(&outputs.len()) {
+        return Err(crate::Error::TooManyOutputs(outputs.len(), INPUT_OUTPUT_COUNT_MAX));
+    }
+    let own_address_recipients = validate_outputs(account_handle, &outputs, &None).await?;
+    let inputs = get_replaceable_inputs(account_handle, &transaction_id).await?;
+    let mut transfer_result = send_replacement(account_handle, transaction_id, inputs, outputs).await?;
+    transfer_result.own_address_recipients = own_address_recipients;
+    Ok(transfer_result)
+}
+
+/// Sends the outputs of a conflicting transaction again with newly selected inputs. Outputs to internal addresses and
+/// to the addresses of the inputs are treated as remainder, the new transaction gets its own remainder.
+pub(crate) async fn resend_transaction(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+) -> crate::Result<TransferResult> {
+    log::debug!("[TRANSFER] resend_transaction {}", transaction_id);
+    let bech32_hrp = crate::client::get_client().await?.get_bech32_hrp().await?;
+    let (outputs, options) = {
+        let account = account_handle.read().await;
+        let transaction = account
+            .transactions
+            .get(&transaction_id)
+            .ok_or(crate::Error::TransactionNotFound(transaction_id))?;
+        if transaction.replaced_by.is_some() {
+            return Err(crate::Error::InvalidTransactionReplacement(format!(
+                "{} was already replaced",
+                transaction_id
+            )));
+        }
+        let Essence::Regular(essence) = transaction.payload.essence();
+        let mut remainder_addresses: HashSet<Address> =
+            account.internal_addresses.iter().map(|a| a.address.inner).collect();
+        for input_id in transaction.input_ids() {
+            if let Some(output) = account.outputs.get(&input_id) {
+                remainder_addresses.insert(output.address);
+            }
+        }
+        let mut outputs = Vec::new();
+        for output in essence.outputs() {
+            let (address, amount, output_kind) = match output {
+                Output::SignatureLockedSingle(output) => {
+                    (*output.address(), output.amount(), OutputKind::SignatureLockedSingle)
+                }
+                Output::SignatureLockedDustAllowance(output) => (
+                    *output.address(),
+                    output.amount(),
+                    OutputKind::SignatureLockedDustAllowance,
+                ),
+                _ => continue,
+            };
+            if !remainder_addresses.contains(&address) {
+                outputs.push(TransferOutput {
+                    address: address.to_bech32(&bech32_hrp),
+                    amount,
+                    output_kind: Some(output_kind),
+                });
+            }
+        }
+        let indexation = match essence.payload() {
+            Some(Payload::Indexation(indexation)) => Some(indexation.as_ref().clone()),
+            _ => None,
+        };
+        (
+            outputs,
+            TransferOptions {
+                indexation,
+                annotation: Some(transaction.annotation.clone()),
+                ..Default::default()
+            },
+        )
+    };
+    if outputs.is_empty() {
+        return Err(crate::Error::InvalidTransactionReplacement(format!(
+            "{} has no outputs to other addresses",
+            transaction_id
+        )));
+    }
+    let transfer_result = send_transfer(account_handle, outputs, Some(options)).await?;
+
+    // link the transactions like a replacement, so the conflicting transaction isn't sent again
+    let mut account = account_handle.write().await;
+    link_replacement(&mut account, transaction_id, transfer_result.transaction_id);
+    #[cfg(feature = "storage")]
+    log::debug!("[TRANSFER] storing account {}", account.index());
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_account(&account)
+        .await?;
+    Ok(transfer_result)
+}
+
+// Get the inputs of a transaction, if it's still pending and wasn't replaced already
+async fn get_replaceable_inputs(
+    account_handle: &AccountHandle,
+    transaction_id: &TransactionId,
+) -> crate::Result<Vec<OutputData>> {
+    let client = crate::client::get_client().await?;
+    let network_id = client.get_network_id().await?;
+    // the account isn't locked while the node is requested
+    let inputs = replaceable_inputs(&account_handle.read().await, transaction_id, network_id)?;
+    // the transaction could have been confirmed since the last syncing, then the first output already exists
+    if client
+        .get_output(&UtxoInput::from(OutputId::new(*transaction_id, 0)?))
+        .await
+        .is_ok()
+    {
+        return Err(crate::Error::TransactionNotPending(*transaction_id));
+    }
+    Ok(inputs)
+}
+
+// Check with the local state if a transaction can be replaced and get its inputs
+fn replaceable_inputs(
+    account: &Account,
+    transaction_id: &TransactionId,
+    network_id: u64,
+) -> crate::Result<Vec<OutputData>> {
+    let transaction = account
+        .transactions
+        .get(transaction_id)
+        .ok_or(crate::Error::TransactionNotFound(*transaction_id))?;
+    if transaction.incoming
+        || transaction.network_id != network_id
+        || !account.pending_transactions.contains(transaction_id)
+    {
+        return Err(crate::Error::TransactionNotPending(*transaction_id));
+    }
+    if let Some(replaced_by) = transaction.replaced_by {
+        return Err(crate::Error::InvalidTransactionReplacement(format!(
+            "{} was already replaced by {}",
+            transaction_id, replaced_by
+        )));
+    }
+
+    let mut inputs = Vec::new();
+    for input_id in transaction.input_ids() {
+        match account.unspent_outputs.get(&input_id) {
+            Some(output) => inputs.push(output.clone()),
+            // an input got spent already, so the transaction can't be pending anymore
+            None => return Err(crate::Error::TransactionNotPending(*transaction_id)),
+        }
+    }
+    Ok(inputs)
+}
+
+// Get an unused internal address for the cancellation of a transaction. Addresses which the transaction sends to are
+// skipped, so the cancellation can't result in the same transaction essence.
+fn get_unused_internal_address(account: &Account, transaction_id: &TransactionId) -> Option<AccountAddress> {
+    let mut output_addresses = HashSet::new();
+    if let Some(transaction) = account.transactions.get(transaction_id) {
+        let Essence::Regular(essence) = transaction.payload.essence();
+        for output in essence.outputs() {
+            let address = match output {
+                Output::SignatureLockedSingle(output) => *output.address(),
+                Output::SignatureLockedDustAllowance(output) => *output.address(),
+                _ => continue,
+            };
+            output_addresses.insert(address);
+        }
+    }
+    account
+        .internal_addresses
+        .iter()
+        .find(|address| !address.used && !output_addresses.contains(&address.address.inner))
+        .cloned()
+}
+
+/// Links a transaction to the pending transaction which it cancels or replaces, so syncing knows that only one of them
+/// can get confirmed
+pub(crate) fn link_replacement(
+    account: &mut Account,
+    replaced_transaction_id: TransactionId,
+    transaction_id: TransactionId,
+) {
+    if let Some(transaction) = account.transactions.get_mut(&transaction_id) {
+        transaction.replaces.replace(replaced_transaction_id);
+    }
+    if let Some(replaced_transaction) = account.transactions.get_mut(&replaced_transaction_id) {
+        replaced_transaction.replaced_by.replace(transaction_id);
+    }
+}
+
+// Build, sign and send a transaction with the inputs of the transaction that should be replaced. The inputs stay
+// locked also if this fails, because the original transaction is still pending.
+async fn send_replacement(
+    account_handle: &AccountHandle,
+    transaction_id: TransactionId,
+    inputs: Vec<OutputData>,
+    outputs: Vec<TransferOutput>,
+) -> crate::Result<TransferResult> {
+    let _spending_reservation = check_spending_policy(account_handle, &outputs, Some(transaction_id)).await?;
+    let (essence, inputs_for_signing, remainder) =
+        create_transaction(account_handle, inputs.clone(), outputs.clone(), None).await?;
+    validate_dust_allowance(&outputs, &remainder, &None).await?;
+    approve_transaction(account_handle, &essence, &inputs, &outputs, &remainder).await?;
+    let transaction_payload = sign_tx_essence(account_handle, essence, inputs_for_signing, remainder).await?;
+    if transaction_payload.id() == transaction_id {
+        return Err(crate::Error::InvalidTransactionReplacement(format!(
+            "replacement of {} has the same outputs",
+            transaction_id
+        )));
+    }
+    // the replacement keeps the note of the original transaction
+    let annotation = account_handle
+        .read()
+        .await
+        .transactions
+        .get(&transaction_id)
+        .map(|transaction| transaction.annotation.clone())
+        .unwrap_or_default();
+    submit_and_store_transaction(
+        account_handle,
+        transaction_payload,
+        Some(transaction_id),
+        annotation,
+        None,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_unused_internal_address, link_replacement, replaceable_inputs};
+    use crate::account::test_utils::{account, account_address, address, output, output_id, transaction};
+
+    use iota_client::bee_message::output::OutputId;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn replaceable_transaction_inputs() {
+        let mut account = account();
+        let input_ids = [output_id(1, 0), output_id(1, 1)];
+        for input_id in input_ids {
+            account
+                .unspent_outputs
+                .insert(input_id, output(input_id, address(1), 1_000_000));
+        }
+        let transaction = transaction(&input_ids, &[(address(2), 2_000_000)]);
+        let transaction_id = transaction.payload.id();
+        account.transactions.insert(transaction_id, transaction);
+        assert!(matches!(
+            replaceable_inputs(&account, &transaction_id, 0),
+            Err(crate::Error::TransactionNotPending(_))
+        ));
+
+        account.pending_transactions.insert(transaction_id);
+        // the replacement spends the same inputs as the original transaction
+        let inputs: HashSet<OutputId> = replaceable_inputs(&account, &transaction_id, 0)
+            .unwrap()
+            .iter()
+            .map(|input| input.output_id)
+            .collect();
+        assert_eq!(inputs, input_ids.into_iter().collect());
+        // transactions of other networks can't be replaced
+        assert!(matches!(
+            replaceable_inputs(&account, &transaction_id, 1),
+            Err(crate::Error::TransactionNotPending(_))
+        ));
+
+        account.unspent_outputs.remove(&input_ids[1]);
+        assert!(matches!(
+            replaceable_inputs(&account, &transaction_id, 0),
+            Err(crate::Error::TransactionNotPending(_))
+        ));
+    }
+
+    #[test]
+    fn replacement_linking() {
+        let mut account = account();
+        account
+            .unspent_outputs
+            .insert(output_id(1, 0), output(output_id(1, 0), address(1), 1_000_000));
+        let original = transaction(&[output_id(1, 0)], &[(address(2), 1_000_000)]);
+        let replacement = transaction(&[output_id(1, 0)], &[(address(3), 1_000_000)]);
+        let original_id = original.payload.id();
+        let replacement_id = replacement.payload.id();
+        account.transactions.insert(original_id, original);
+        account.transactions.insert(replacement_id, replacement);
+        account.pending_transactions.insert(original_id);
+        account.pending_transactions.insert(replacement_id);
+
+        link_replacement(&mut account, original_id, replacement_id);
+        assert_eq!(account.transactions[&replacement_id].replaces, Some(original_id));
+        assert_eq!(account.transactions[&original_id].replaced_by, Some(replacement_id));
+        // a transaction can only be replaced once
+        assert!(matches!(
+            replaceable_inputs(&account, &original_id, 0),
+            Err(crate::Error::InvalidTransactionReplacement(_))
+        ));
+        assert!(replaceable_inputs(&account, &replacement_id, 0).is_ok());
+    }
+
+    #[test]
+    fn cancellation_address() {
+        let mut account = account();
+        let mut used_address = account_address(1, 0, true);
+        used_address.used = true;
+        account.internal_addresses = vec![used_address, account_address(2, 1, true), account_address(3, 2, true)];
+        let transaction = transaction(&[output_id(1, 0)], &[(address(2), 1_000_000)]);
+        let transaction_id = transaction.payload.id();
+        account.transactions.insert(transaction_id, transaction);
+
+        // used addresses and addresses which the cancelled transaction sends to are skipped
+        assert_eq!(
+            get_unused_internal_address(&account, &transaction_id).map(|address| address.key_index),
+            Some(2)
+        );
+        account.internal_addresses.truncate(2);
+        assert!(get_unused_internal_address(&account, &transaction_id).is_none());
+    }
+}
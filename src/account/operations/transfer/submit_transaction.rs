@@ -1,7 +1,10 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::account::{handle::AccountHandle, operations::transfer::TransactionPayload};
+use crate::account::{
+    handle::AccountHandle,
+    operations::{retry::start_retry_task, transfer::TransactionPayload},
+};
 #[cfg(feature = "events")]
 use crate::events::types::{TransferProgressEvent, WalletEvent};
 
@@ -10,16 +13,24 @@ use iota_client::{
     bee_message::{payload::Payload, MessageId},
 };
 
-/// Submits a payload in a message
+/// Submits a transaction payload in a message and spawns a task which tries to get it confirmed
 pub(crate) async fn submit_transaction_payload(
     account_handle: &AccountHandle,
     transaction_payload: TransactionPayload,
 ) -> crate::Result<MessageId> {
+    let reattach_policy = account_handle.read().await.account_options.reattach_policy;
+    let transaction_id = transaction_payload.id();
+    let message_id = submit_payload(account_handle, Payload::Transaction(Box::new(transaction_payload))).await?;
+    // the task gets aborted together with the account
+    start_retry_task(account_handle, transaction_id, reattach_policy);
+    Ok(message_id)
+}
+
+/// Submits a payload in a message, the proof of work is done locally if the client is configured for it
+pub(crate) async fn submit_payload(account_handle: &AccountHandle, payload: Payload) -> crate::Result<MessageId> {
     log::debug!("[TRANSFER] send_payload");
-    let account = account_handle.read().await;
     #[cfg(feature = "events")]
-    let account_index = account.index;
-    drop(account);
+    let account_index = account_handle.read().await.index;
     let client = crate::client::get_client().await?;
     let local_pow = client.get_local_pow().await;
     if local_pow {
@@ -30,7 +41,7 @@ pub(crate) async fn submit_transaction_payload(
             WalletEvent::TransferProgress(TransferProgressEvent::PerformingPoW),
         );
     }
-    let message = finish_pow(&client, Some(Payload::Transaction(Box::new(transaction_payload)))).await?;
+    let message = finish_pow(&client, Some(payload)).await?;
     // log::debug!("[TRANSFER] submitting message {:#?}", message);
     #[cfg(feature = "events")]
     account_handle.event_emitter.lock().await.emit(
@@ -39,16 +50,5 @@ pub(crate) async fn submit_transaction_payload(
     );
     let message_id = client.post_message(&message).await?;
     log::debug!("[TRANSFER] submitted message {}", message_id);
-    drop(client);
-    // spawn a thread which tries to get the message confirmed
-    tokio::spawn(async move {
-        if let Ok(client) = crate::client::get_client().await {
-            if let Ok(messages) = client.retry_until_included(&message_id, None, None).await {
-                for msg in messages {
-                    log::debug!("[TRANSFER] reattached {}, new message id {}", message_id, msg.0);
-                }
-            }
-        }
-    });
     Ok(message_id)
 }
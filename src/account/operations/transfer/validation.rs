@@ -0,0 +1,184 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    constants::MIN_DUST_ALLOWANCE_VALUE,
+    handle::AccountHandle,
+    operations::{
+        micropayments::allowed_dust_outputs,
+        syncing::outputs::get_output_amount_and_address,
+        transfer::{Remainder, RemainderValueStrategy, TransferOptions, TransferOutput},
+    },
+    types::{address::parse_bech32_address, OutputKind},
+};
+
+use std::collections::HashMap;
+
+/// Validates the recipient addresses of a transfer before inputs get selected. The addresses need to have the bech32
+/// hrp of the network the client is connected to and a custom remainder address needs to belong to the account.
+/// Recipients which are addresses of the account are returned as warning, unless the options skip it.
+pub(crate) async fn validate_outputs(
+    account_handle: &AccountHandle,
+    outputs: &[TransferOutput],
+    options: &Option<TransferOptions>,
+) -> crate::Result<Vec<String>> {
+    log::debug!("[TRANSFER] validate_outputs");
+    let client = crate::client::get_client().await?;
+    let bech32_hrp = client.get_bech32_hrp().await?;
+    let account = account_handle.read().await;
+    let warn_own_address = !options
+        .as_ref()
+        .map_or(false, |options| options.skip_own_address_warning);
+
+    let mut own_address_recipients = Vec::new();
+    for output in outputs {
+        let address = parse_bech32_address(&output.address)?;
+        if address.bech32_hrp() != bech32_hrp {
+            return Err(crate::Error::InvalidAddressNetwork(output.address.clone(), bech32_hrp));
+        }
+        if warn_own_address && account.is_account_address(&address.inner) {
+            log::warn!(
+                "[TRANSFER] recipient {} is an address of account {}",
+                output.address,
+                account.index()
+            );
+            own_address_recipients.push(output.address.clone());
+        }
+    }
+
+    if let Some(TransferOptions {
+        remainder_value_strategy: RemainderValueStrategy::CustomAddress(remainder_address),
+        ..
+    }) = options
+    {
+        if !account.is_account_address(&remainder_address.address.inner) {
+            return Err(crate::Error::InvalidRemainderValueAddress);
+        }
+    }
+    Ok(own_address_recipients)
+}
+
+/// Checks with the node that every recipient of a dust output (a SignatureLockedSingle output below
+/// `MIN_DUST_ALLOWANCE_VALUE`) has enough dust allowance to receive it, otherwise the node would reject the transaction.
+/// The remainder output of the transaction is checked like the other outputs.
+pub(crate) async fn validate_dust_allowance(
+    outputs: &[TransferOutput],
+    remainder: &Option<Remainder>,
+    options: &Option<TransferOptions>,
+) -> crate::Result<()> {
+    log::debug!("[TRANSFER] validate_dust_allowance");
+    let remainder_output = remainder.as_ref().map(|remainder| TransferOutput {
+        address: remainder.address.address.to_bech32(),
+        amount: remainder.amount,
+        output_kind: options
+            .as_ref()
+            .and_then(|options| options.remainder_output_kind.clone()),
+    });
+    // amount of new dust outputs and dust allowance for each recipient
+    let recipients = count_recipient_dust(outputs.iter().chain(&remainder_output));
+
+    let client = crate::client::get_client().await?;
+    for (address, (new_dust_outputs, new_dust_allowance)) in recipients {
+        if new_dust_outputs == 0 {
+            continue;
+        }
+        let mut dust_outputs = 0;
+        let mut dust_allowance = new_dust_allowance;
+        for output in client.find_outputs(&[], &[address.to_string()]).await? {
+            if output.is_spent {
+                continue;
+            }
+            let (amount, _, output_kind) = get_output_amount_and_address(&output.output)?;
+            match output_kind {
+                OutputKind::SignatureLockedDustAllowance => dust_allowance += amount,
+                OutputKind::SignatureLockedSingle if amount < MIN_DUST_ALLOWANCE_VALUE => dust_outputs += 1,
+                _ => {}
+            }
+        }
+        check_recipient_dust(address, dust_outputs, dust_allowance, new_dust_outputs)?;
+    }
+    Ok(())
+}
+
+// Count the new dust outputs and the new dust allowance for each recipient
+fn count_recipient_dust<'a>(outputs: impl Iterator<Item = &'a TransferOutput>) -> HashMap<&'a str, (usize, u64)> {
+    let mut recipients: HashMap<&str, (usize, u64)> = HashMap::new();
+    for output in outputs {
+        let (dust_outputs, dust_allowance) = recipients.entry(&output.address).or_default();
+        match output.output_kind {
+            Some(OutputKind::SignatureLockedDustAllowance) => *dust_allowance += output.amount,
+            _ => {
+                if output.amount < MIN_DUST_ALLOWANCE_VALUE {
+                    *dust_outputs += 1;
+                }
+            }
+        }
+    }
+    recipients
+}
+
+// Check if an address can receive new dust outputs, the dust outputs and the dust allowance include the existing ones
+// on the address
+fn check_recipient_dust(
+    address: &str,
+    dust_outputs: usize,
+    dust_allowance: u64,
+    new_dust_outputs: usize,
+) -> crate::Result<()> {
+    let allowed_dust_outputs = allowed_dust_outputs(dust_allowance);
+    log::debug!(
+        "[TRANSFER] {} has {} dust outputs and {}i dust allowance, {} dust outputs are allowed",
+        address,
+        dust_outputs,
+        dust_allowance,
+        allowed_dust_outputs
+    );
+    if dust_outputs + new_dust_outputs > allowed_dust_outputs {
+        return Err(crate::Error::DustError(if dust_allowance == 0 {
+            format!(
+                "{} has no dust allowance output and can't receive outputs below 1 Mi",
+                address
+            )
+        } else {
+            format!(
+                "{} can only have {} dust outputs with a dust allowance of {}i and has {} already",
+                address, allowed_dust_outputs, dust_allowance, dust_outputs
+            )
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_recipient_dust, count_recipient_dust};
+    use crate::account::{types::OutputKind, TransferOutput};
+
+    #[test]
+    fn recipient_dust_count() {
+        let output = |address: &str, amount, output_kind| TransferOutput {
+            address: address.to_string(),
+            amount,
+            output_kind,
+        };
+        let outputs = vec![
+            output("dust", 1, None),
+            output("dust", 999_999, Some(OutputKind::SignatureLockedSingle)),
+            output("dust", 1_000_000, None),
+            output("allowance", 1_000_000, Some(OutputKind::SignatureLockedDustAllowance)),
+            output("allowance", 1, None),
+        ];
+        let recipients = count_recipient_dust(outputs.iter());
+        assert_eq!(recipients["dust"], (2, 0));
+        assert_eq!(recipients["allowance"], (1, 1_000_000));
+    }
+
+    #[test]
+    fn recipient_dust_check() {
+        assert!(check_recipient_dust("address", 0, 0, 0).is_ok());
+        assert!(check_recipient_dust("address", 0, 0, 1).is_err());
+        // 1 Mi dust allowance allows 10 dust outputs
+        assert!(check_recipient_dust("address", 9, 1_000_000, 1).is_ok());
+        assert!(check_recipient_dust("address", 9, 1_000_000, 2).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    account::{
+        types::{
+            address::{AccountAddress, AddressWrapper},
+            InclusionState, OutputData, OutputKind, TimestampSource, Transaction,
+        },
+        Account, AccountOptions,
+    },
+    signing::SignerType,
+};
+
+use iota_client::{
+    bee_message::{
+        address::{Address, Ed25519Address},
+        input::{Input, UtxoInput},
+        output::{Output, OutputId, SignatureLockedSingleOutput},
+        payload::transaction::{Essence, RegularEssence, TransactionId, TransactionPayload},
+        signature::{Ed25519Signature, SignatureUnlock},
+        unlock::{ReferenceUnlock, UnlockBlock, UnlockBlocks},
+        MessageId,
+    },
+    common::packable::Packable,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// An empty account
+pub(crate) fn account() -> Account {
+    Account {
+        id: "0".to_string(),
+        index: 0,
+        alias: "0".to_string(),
+        signer_type: SignerType::Mnemonic,
+        public_addresses: Vec::new(),
+        internal_addresses: Vec::new(),
+        addresses_with_balance: Vec::new(),
+        outputs: HashMap::new(),
+        locked_outputs: HashSet::new(),
+        frozen_outputs: HashSet::new(),
+        output_locks: HashMap::new(),
+        unspent_outputs: HashMap::new(),
+        transactions: HashMap::new(),
+        pending_transactions: HashSet::new(),
+        idempotency_keys: HashMap::new(),
+        account_options: AccountOptions::default(),
+    }
+}
+
+/// An ed25519 address, the byte makes it unique
+pub(crate) fn address(byte: u8) -> Address {
+    Address::Ed25519(Ed25519Address::new([byte; 32]))
+}
+
+/// An unused address of the account with the address from `address(byte)`
+pub(crate) fn account_address(byte: u8, key_index: usize, internal: bool) -> AccountAddress {
+    AccountAddress {
+        address: AddressWrapper::new(address(byte), "atoi".to_string()),
+        key_index,
+        internal,
+        used: false,
+    }
+}
+
+/// An output id, the byte makes the transaction id unique
+pub(crate) fn output_id(transaction: u8, index: u16) -> OutputId {
+    OutputId::new(TransactionId::new([transaction; 32]), index).unwrap()
+}
+
+/// An unspent SignatureLockedSingle output
+pub(crate) fn output(output_id: OutputId, address: Address, amount: u64) -> OutputData {
+    OutputData {
+        output_id,
+        message_id: MessageId::new([0; 32]),
+        amount,
+        is_spent: false,
+        address,
+        kind: OutputKind::SignatureLockedSingle,
+        network_id: 0,
+        timestamp: 0,
+        timestamp_source: TimestampSource::Local,
+        remainder: false,
+        annotation: Default::default(),
+    }
+}
+
+/// An outgoing pending transaction which spends the inputs to SignatureLockedSingle outputs
+pub(crate) fn transaction(inputs: &[OutputId], outputs: &[(Address, u64)]) -> Transaction {
+    let mut inputs: Vec<Input> = inputs
+        .iter()
+        .map(|output_id| Input::Utxo(UtxoInput::from(*output_id)))
+        .collect();
+    inputs.sort_unstable_by_key(|input| input.pack_new());
+    let mut outputs: Vec<Output> = outputs
+        .iter()
+        .map(|(address, amount)| SignatureLockedSingleOutput::new(*address, *amount).unwrap().into())
+        .collect();
+    outputs.sort_unstable_by_key(|output| output.pack_new());
+    // all inputs belong to the same address, so the first one has the signature
+    let unlock_blocks = (0..inputs.len())
+        .map(|index| match index {
+            0 => UnlockBlock::Signature(SignatureUnlock::Ed25519(Ed25519Signature::new([0; 32], [0; 64]))),
+            _ => UnlockBlock::Reference(ReferenceUnlock::new(0).unwrap()),
+        })
+        .collect();
+    let essence = RegularEssence::builder()
+        .with_inputs(inputs)
+        .with_outputs(outputs)
+        .finish()
+        .unwrap();
+    Transaction {
+        payload: TransactionPayload::builder()
+            .with_essence(Essence::Regular(essence))
+            .with_unlock_blocks(UnlockBlocks::new(unlock_blocks).unwrap())
+            .finish()
+            .unwrap(),
+        message_id: None,
+        inclusion_state: InclusionState::Pending,
+        timestamp: 0,
+        timestamp_source: TimestampSource::Local,
+        network_id: 0,
+        incoming: false,
+        internal: false,
+        replaces: None,
+        replaced_by: None,
+        reattachments: 0,
+        last_reattachment: None,
+        annotation: Default::default(),
+        senders: Vec::new(),
+        received_amount: None,
+        conflict_reason: None,
+        contacts: HashMap::new(),
+    }
+}
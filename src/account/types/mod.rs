@@ -8,11 +8,15 @@ pub(crate) mod address_serde;
 use crate::account::constants::ACCOUNT_ID_PREFIX;
 
 use iota_client::bee_message::{
-    address::Address, output::OutputId, payload::transaction::TransactionPayload, MessageId,
+    address::Address,
+    input::Input,
+    output::OutputId,
+    payload::transaction::{Essence, TransactionId, TransactionPayload},
+    MessageId,
 };
 use serde::{Deserialize, Deserializer, Serialize};
 
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 /// The balance of an account, returned from [`crate::account::handle::AccountHandle::sync()`] and
 /// [`crate::account::handle::AccountHandle::balance()`].
@@ -20,6 +24,26 @@ use std::str::FromStr;
 pub struct AccountBalance {
     pub(crate) total: u64,
     pub(crate) available: u64,
+    /// Balance of frozen outputs which aren't locked for a transaction
+    pub(crate) frozen: u64,
+}
+
+/// The dust protection state of an address, returned from
+/// [`crate::account::handle::AccountHandle::dust_allowance_status()`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressDustAllowance {
+    /// The address.
+    #[serde(with = "crate::account::types::address_serde")]
+    pub address: address::AddressWrapper,
+    /// The sum of the dust allowance outputs on the address.
+    #[serde(rename = "dustAllowance")]
+    pub dust_allowance: u64,
+    /// The amount of dust outputs on the address.
+    #[serde(rename = "dustOutputs")]
+    pub dust_outputs: usize,
+    /// The amount of dust outputs the address can still receive.
+    #[serde(rename = "remainingDustOutputs")]
+    pub remaining_dust_outputs: usize,
 }
 
 /// An output with metadata
@@ -41,10 +65,15 @@ pub struct OutputData {
     /// Network ID
     #[serde(rename = "networkId")]
     pub network_id: u64,
-    // get it from the milestone that confirmed it (get metadata of the oupt and calculate the time backwards if the
-    // milestone was pruned)
+    /// Timestamp of the milestone that confirmed the output in ms
     pub timestamp: u128,
+    /// Where the timestamp comes from
+    #[serde(rename = "timestampSource", default)]
+    pub timestamp_source: TimestampSource,
     pub remainder: bool,
+    /// Local note, label and tags
+    #[serde(default)]
+    pub annotation: Annotation,
 }
 
 /// A transaction with metadata
@@ -53,13 +82,119 @@ pub struct Transaction {
     pub payload: TransactionPayload,
     pub message_id: Option<MessageId>,
     pub inclusion_state: InclusionState,
+    // creation time, set to the timestamp of the confirming milestone once the transaction is confirmed
     pub timestamp: u128,
+    #[serde(default)]
+    pub timestamp_source: TimestampSource,
     // network id to ignore outputs when set_client_options is used to switch to another network
     pub network_id: u64,
     // set if the transaction was created by the wallet or if it was sent by someone else and is incoming
     pub incoming: bool,
     // do we want this field? could be used for internal transfers later, but not really necessary
     pub internal: bool,
+    // the transaction that got cancelled or replaced by this one, both spend the same inputs so only one can confirm
+    #[serde(default)]
+    pub replaces: Option<TransactionId>,
+    // set if this transaction got cancelled or replaced by another one while it was pending
+    #[serde(default)]
+    pub replaced_by: Option<TransactionId>,
+    // how often the transaction got reattached, used for the ReattachPolicy
+    #[serde(default)]
+    pub reattachments: usize,
+    // time of the latest reattachment, the delay for the next reattachment starts from it
+    #[serde(default)]
+    pub last_reattachment: Option<u128>,
+    // note, label and tags, only stored locally and never sent to the network
+    #[serde(default)]
+    pub annotation: Annotation,
+    // bech32 addresses which signed the inputs of an incoming transaction
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub senders: Vec<String>,
+    // amount an incoming transaction sent to addresses of the account
+    #[serde(default)]
+    pub received_amount: Option<u64>,
+    // why the node rejected a conflicting transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict_reason: Option<ConflictReason>,
+    // contact names from the address book for output addresses, only set in transaction listings
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub contacts: HashMap<String, String>,
+}
+
+impl Transaction {
+    /// The ids of the outputs which the transaction spends
+    pub(crate) fn input_ids(&self) -> Vec<OutputId> {
+        let Essence::Regular(essence) = self.payload.essence();
+        essence
+            .inputs()
+            .iter()
+            .filter_map(|input| match input {
+                Input::Utxo(input) => Some(*input.output_id()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// User metadata for a transaction or an output, it's only stored locally
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// A free-form note
+    #[serde(default)]
+    pub note: Option<String>,
+    /// A category label
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Key/value tags
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+/// Filter for [`crate::account::handle::AccountHandle::list_transactions_filtered()`], all set fields need to match
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TransactionFilter {
+    /// Only transactions with this label
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Only transactions which have all of these tags with the same values
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// Only transactions with this inclusion state
+    #[serde(rename = "inclusionState", default)]
+    pub inclusion_state: Option<InclusionState>,
+}
+
+impl TransactionFilter {
+    /// Check if a transaction matches the filter
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        if let Some(label) = &self.label {
+            if transaction.annotation.label.as_ref() != Some(label) {
+                return false;
+            }
+        }
+        if let Some(inclusion_state) = &self.inclusion_state {
+            if &transaction.inclusion_state != inclusion_state {
+                return false;
+            }
+        }
+        self.tags
+            .iter()
+            .all(|(key, value)| transaction.annotation.tags.get(key) == Some(value))
+    }
+}
+
+/// A lock on an output that is used as input for a transaction, so it doesn't get used by another transaction. Locks
+/// are persisted in a journal right away, so they aren't lost if the process stops before the account is stored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputLock {
+    /// The transaction which uses the output, not set until the transaction is signed
+    #[serde(rename = "transactionId")]
+    pub transaction_id: Option<TransactionId>,
+    /// Time in milliseconds when the output got locked
+    #[serde(rename = "lockedAt")]
+    pub locked_at: u128,
+    /// Time in milliseconds after which the lock gets released on startup, if its transaction wasn't sent
+    pub ttl: u128,
 }
 
 /// Possible InclusionStates for transactions
@@ -70,6 +205,66 @@ pub enum InclusionState {
     Conflicting,
 }
 
+/// Where the timestamp of an output or a transaction comes from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum TimestampSource {
+    /// Timestamp of the milestone which referenced the message
+    Milestone,
+    /// The milestone got pruned, the timestamp is estimated from the latest milestone and the milestone index
+    EstimatedMilestone,
+    /// The message got pruned or isn't referenced yet, the local time when the wallet created or found it
+    Local,
+}
+
+impl Default for TimestampSource {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+/// Why a transaction is conflicting, the codes are from the message metadata of the node
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ConflictReason {
+    /// An input was already spent
+    InputUtxoAlreadySpent,
+    /// An input was already spent by another transaction in the same milestone
+    InputUtxoAlreadySpentInThisMilestone,
+    /// An input doesn't exist
+    InputUtxoNotFound,
+    /// The sum of the inputs and outputs doesn't match
+    InputOutputSumMismatch,
+    /// A signature is invalid
+    InvalidSignature,
+    /// The dust allowance of an address is invalid
+    InvalidDustAllowance,
+    /// The semantic validation failed for another reason
+    SemanticValidationFailed,
+}
+
+impl ConflictReason {
+    /// Returns the reason for a conflict code, `None` for 0 (no conflict)
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => None,
+            1 => Some(Self::InputUtxoAlreadySpent),
+            2 => Some(Self::InputUtxoAlreadySpentInThisMilestone),
+            3 => Some(Self::InputUtxoNotFound),
+            4 => Some(Self::InputOutputSumMismatch),
+            5 => Some(Self::InvalidSignature),
+            6 => Some(Self::InvalidDustAllowance),
+            _ => Some(Self::SemanticValidationFailed),
+        }
+    }
+
+    /// Returns true if the conflict was caused by the inputs, then the transaction can be sent again with other inputs
+    pub(crate) fn is_input_conflict(&self) -> bool {
+        matches!(
+            self,
+            Self::InputUtxoAlreadySpent | Self::InputUtxoAlreadySpentInThisMilestone | Self::InputUtxoNotFound
+        )
+    }
+}
+
 /// The output kind enum.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OutputKind {
@@ -148,3 +343,72 @@ impl From<usize> for AccountIdentifier {
         Self::Index(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ConflictReason, InclusionState, TransactionFilter};
+    use crate::account::test_utils::{address, output_id, transaction};
+
+    #[test]
+    fn conflict_reason_codes() {
+        assert_eq!(ConflictReason::from_code(0), None);
+        assert_eq!(
+            ConflictReason::from_code(1),
+            Some(ConflictReason::InputUtxoAlreadySpent)
+        );
+        assert_eq!(ConflictReason::from_code(3), Some(ConflictReason::InputUtxoNotFound));
+        assert_eq!(ConflictReason::from_code(6), Some(ConflictReason::InvalidDustAllowance));
+        // unknown codes are semantic validation failures
+        assert_eq!(
+            ConflictReason::from_code(255),
+            Some(ConflictReason::SemanticValidationFailed)
+        );
+
+        assert!(ConflictReason::InputUtxoAlreadySpentInThisMilestone.is_input_conflict());
+        assert!(!ConflictReason::InvalidSignature.is_input_conflict());
+        assert!(!ConflictReason::SemanticValidationFailed.is_input_conflict());
+    }
+
+    #[test]
+    fn transaction_filter() {
+        let mut transaction = transaction(&[output_id(1, 0)], &[(address(1), 1_000_000)]);
+        transaction.annotation.label.replace("rent".to_string());
+        transaction
+            .annotation
+            .tags
+            .insert("month".to_string(), "may".to_string());
+        transaction
+            .annotation
+            .tags
+            .insert("paid".to_string(), "yes".to_string());
+        assert!(TransactionFilter::default().matches(&transaction));
+
+        let filter = TransactionFilter {
+            label: Some("rent".to_string()),
+            tags: vec![("month".to_string(), "may".to_string())].into_iter().collect(),
+            inclusion_state: Some(InclusionState::Pending),
+        };
+        assert!(filter.matches(&transaction));
+        // all set fields need to match
+        for filter in [
+            TransactionFilter {
+                label: Some("food".to_string()),
+                ..filter.clone()
+            },
+            TransactionFilter {
+                tags: vec![("month".to_string(), "june".to_string())].into_iter().collect(),
+                ..filter.clone()
+            },
+            TransactionFilter {
+                tags: vec![("year".to_string(), "2021".to_string())].into_iter().collect(),
+                ..filter.clone()
+            },
+            TransactionFilter {
+                inclusion_state: Some(InclusionState::Confirmed),
+                ..filter.clone()
+            },
+        ] {
+            assert!(!filter.matches(&transaction));
+        }
+    }
+}
@@ -3,14 +3,18 @@
 
 #[cfg(feature = "events")]
 use crate::events::EventEmitter;
-#[cfg(feature = "storage")]
-use crate::storage::manager::ManagerStorage;
 use crate::{
     account::handle::AccountHandle,
     account_manager::AccountManager,
     client::options::{ClientOptions, ClientOptionsBuilder},
     signing::SignerType,
 };
+#[cfg(feature = "storage")]
+use crate::{
+    account::operations::output_locks::restore_output_locks,
+    account_manager::operations::address_book::{self, AddressBook},
+    storage::manager::ManagerStorage,
+};
 
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "events")]
@@ -123,12 +127,18 @@ impl AccountManagerBuilder {
                 Box::new(storage) as Box<dyn crate::storage::adapter::StorageAdapter + Send + Sync>,
             )
             .await?;
-            let data = crate::storage::manager::load_account_manager(
+            let mut data = crate::storage::manager::load_account_manager(
                 options.manager_store,
                 options.storage_folder,
                 options.storage_file_name,
             )
             .await?;
+            // lock outputs again which were used for transactions that weren't stored in the account
+            for account in data.1.iter_mut() {
+                restore_output_locks(account).await?;
+            }
+            let address_book = Arc::new(AddressBook::default());
+            address_book::load_address_book(&address_book).await?;
             let (client_options, signer_type) = match data.0 {
                 Some(data) => (data.client_options, data.signer_type),
                 None => (self.client_options, self.signer_type),
@@ -137,17 +147,23 @@ impl AccountManagerBuilder {
             let event_emitter = Arc::new(Mutex::new(EventEmitter::new()));
             return Ok(AccountManager {
                 #[cfg(not(feature = "events"))]
-                accounts: Arc::new(RwLock::new(data.1.into_iter().map(AccountHandle::new).collect())),
+                accounts: Arc::new(RwLock::new(
+                    data.1
+                        .into_iter()
+                        .map(|a| AccountHandle::new(a, address_book.clone()))
+                        .collect(),
+                )),
                 #[cfg(feature = "events")]
                 accounts: Arc::new(RwLock::new(
                     data.1
                         .into_iter()
-                        .map(|a| AccountHandle::new(a, event_emitter.clone()))
+                        .map(|a| AccountHandle::new(a, address_book.clone(), event_emitter.clone()))
                         .collect(),
                 )),
                 background_syncing_status: Arc::new(AtomicUsize::new(0)),
                 client_options: Arc::new(RwLock::new(client_options)),
                 signer_type,
+                address_book,
                 #[cfg(feature = "events")]
                 event_emitter,
             });
@@ -157,6 +173,7 @@ impl AccountManagerBuilder {
             background_syncing_status: Arc::new(AtomicUsize::new(0)),
             client_options: Arc::new(RwLock::new(self.client_options)),
             signer_type: self.signer_type,
+            address_book: Default::default(),
             #[cfg(feature = "events")]
             event_emitter: Arc::new(Mutex::new(EventEmitter::new())),
         })
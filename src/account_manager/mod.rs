@@ -20,7 +20,11 @@ use crate::{
     signing::SignerType,
 };
 use builder::AccountManagerBuilder;
-use operations::{get_account, recover_accounts, start_background_syncing};
+pub use operations::address_book::Contact;
+use operations::{
+    address_book::{self, AddressBook},
+    get_account, recover_accounts, start_background_syncing,
+};
 
 use iota_client::Client;
 #[cfg(feature = "events")]
@@ -45,6 +49,8 @@ pub struct AccountManager {
     pub(crate) background_syncing_status: Arc<AtomicUsize>,
     pub(crate) client_options: Arc<RwLock<ClientOptions>>,
     pub(crate) signer_type: SignerType,
+    // contacts which can be used instead of addresses in transfers of all accounts
+    pub(crate) address_book: Arc<AddressBook>,
     #[cfg(feature = "events")]
     pub(crate) event_emitter: Arc<Mutex<EventEmitter>>,
 }
@@ -59,11 +65,16 @@ impl AccountManager {
     pub fn create_account(&self) -> AccountBuilder {
         log::debug!("creating account");
         #[cfg(not(feature = "events"))]
-        return AccountBuilder::new(self.accounts.clone(), self.signer_type.clone());
+        return AccountBuilder::new(
+            self.accounts.clone(),
+            self.signer_type.clone(),
+            self.address_book.clone(),
+        );
         #[cfg(feature = "events")]
         AccountBuilder::new(
             self.accounts.clone(),
             self.signer_type.clone(),
+            self.address_book.clone(),
             self.event_emitter.clone(),
         )
     }
@@ -108,14 +119,35 @@ impl AccountManager {
         Ok(())
     }
 
+    /// Adds a contact to the address book or replaces the contact with the same name. The name can be used instead of
+    /// an address in a `TransferOutput`
+    pub async fn add_contact(&self, contact: Contact) -> crate::Result<()> {
+        address_book::add_contact(&self.address_book, contact).await
+    }
+
+    /// Removes a contact from the address book
+    pub async fn remove_contact(&self, name: &str) -> crate::Result<()> {
+        address_book::remove_contact(&self.address_book, name).await
+    }
+
+    /// Returns all contacts of the address book
+    pub async fn list_contacts(&self) -> crate::Result<Vec<Contact>> {
+        Ok(address_book::list_contacts(&self.address_book).await)
+    }
+
     /// Get the balance of all accounts added together
     pub async fn balance(&self) -> crate::Result<AccountBalance> {
-        let mut balance = AccountBalance { total: 0, available: 0 };
+        let mut balance = AccountBalance {
+            total: 0,
+            available: 0,
+            frozen: 0,
+        };
         let accounts = self.accounts.read().await;
         for account in accounts.iter() {
             let account_balance = account.balance().await?;
             balance.total += account_balance.total;
             balance.available += account_balance.available;
+            balance.frozen += account_balance.frozen;
         }
         Ok(balance)
     }
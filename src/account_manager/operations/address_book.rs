@@ -0,0 +1,231 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::account::{
+    types::{address::parse_bech32_address, Transaction},
+    TransferOutput,
+};
+
+use iota_client::bee_message::{output::Output, payload::transaction::Essence};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use std::collections::HashMap;
+
+/// A named contact with one or more bech32 addresses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    /// The name of the contact, used instead of an address in a [`TransferOutput`]
+    pub name: String,
+    /// The bech32 addresses of the contact
+    pub addresses: Vec<String>,
+    /// The bech32 hrp of the network the contact uses, like "iota" or "atoi"
+    #[serde(default)]
+    pub network: Option<String>,
+}
+
+/// The contacts by name, the address book is owned by the account manager and shared with all of its accounts
+pub(crate) type AddressBook = RwLock<HashMap<String, Contact>>;
+
+/// Adds a contact or replaces the contact with the same name
+pub(crate) async fn add_contact(address_book: &AddressBook, contact: Contact) -> crate::Result<()> {
+    log::debug!("[ADDRESS BOOK] add contact {}", contact.name);
+    if contact.name.is_empty() {
+        return Err(crate::Error::ContactError("the name can't be empty".to_string()));
+    }
+    for address in &contact.addresses {
+        let address = parse_bech32_address(address)?;
+        if let Some(network) = &contact.network {
+            if address.bech32_hrp() != network {
+                return Err(crate::Error::InvalidAddressNetwork(
+                    address.to_bech32(),
+                    network.clone(),
+                ));
+            }
+        }
+    }
+    let mut address_book = address_book.write().await;
+    address_book.insert(contact.name.clone(), contact);
+    save_address_book(&address_book).await
+}
+
+/// Removes a contact
+pub(crate) async fn remove_contact(address_book: &AddressBook, name: &str) -> crate::Result<()> {
+    log::debug!("[ADDRESS BOOK] remove contact {}", name);
+    let mut address_book = address_book.write().await;
+    if address_book.remove(name).is_none() {
+        return Err(crate::Error::ContactError(format!("{} not found", name)));
+    }
+    save_address_book(&address_book).await
+}
+
+/// Returns all contacts
+pub(crate) async fn list_contacts(address_book: &AddressBook) -> Vec<Contact> {
+    address_book.read().await.values().cloned().collect()
+}
+
+/// Loads the address book from the storage
+#[cfg(feature = "storage")]
+pub(crate) async fn load_address_book(address_book: &AddressBook) -> crate::Result<()> {
+    let contacts = crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .get_address_book()
+        .await?;
+    *address_book.write().await = contacts;
+    Ok(())
+}
+
+// Stores the address book, the lock on it is hold so the stored version can't be older than the one in memory
+async fn save_address_book(address_book: &HashMap<String, Contact>) -> crate::Result<()> {
+    #[cfg(feature = "storage")]
+    crate::storage::manager::get()
+        .await?
+        .lock()
+        .await
+        .save_address_book(address_book)
+        .await?;
+    Ok(())
+}
+
+/// Replaces contact names in the outputs with the address of the contact for the network with the `bech32_hrp`.
+/// Addresses are kept as they are.
+pub(crate) async fn resolve_contacts(
+    address_book: &AddressBook,
+    outputs: &mut [TransferOutput],
+    bech32_hrp: &str,
+) -> crate::Result<()> {
+    let address_book = address_book.read().await;
+    for output in outputs.iter_mut() {
+        if parse_bech32_address(&output.address).is_ok() {
+            continue;
+        }
+        let contact = address_book
+            .get(&output.address)
+            .ok_or_else(|| crate::Error::ContactError(format!("{} is no address and no contact", output.address)))?;
+        if let Some(network) = &contact.network {
+            if network != bech32_hrp {
+                return Err(crate::Error::ContactError(format!(
+                    "{} uses the network {}, but the client uses {}",
+                    contact.name, network, bech32_hrp
+                )));
+            }
+        }
+        let address = contact
+            .addresses
+            .iter()
+            .find(|address| {
+                parse_bech32_address(address)
+                    .map(|address| address.bech32_hrp() == bech32_hrp)
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| {
+                crate::Error::ContactError(format!(
+                    "{} has no address for the network {}",
+                    contact.name, bech32_hrp
+                ))
+            })?;
+        log::debug!("[ADDRESS BOOK] resolved {} to {}", contact.name, address);
+        output.address = address.clone();
+    }
+    Ok(())
+}
+
+/// Sets the contact names for the output addresses of the transactions which are in the address book
+pub(crate) async fn set_contact_names(address_book: &AddressBook, transactions: &mut [Transaction], bech32_hrp: &str) {
+    let address_book = address_book.read().await;
+    if address_book.is_empty() {
+        return;
+    }
+    let contact_names: HashMap<&String, &String> = address_book
+        .values()
+        .flat_map(|contact| contact.addresses.iter().map(move |address| (address, &contact.name)))
+        .collect();
+    for transaction in transactions.iter_mut() {
+        let Essence::Regular(essence) = transaction.payload.essence();
+        for output in essence.outputs() {
+            let address = match output {
+                Output::SignatureLockedSingle(output) => output.address(),
+                Output::SignatureLockedDustAllowance(output) => output.address(),
+                _ => continue,
+            }
+            .to_bech32(bech32_hrp);
+            if let Some(name) = contact_names.get(&address) {
+                transaction.contacts.insert(address, (*name).clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_contacts, set_contact_names, AddressBook, Contact};
+    use crate::account::{
+        test_utils::{address, output_id, transaction},
+        TransferOutput,
+    };
+
+    fn contact(name: &str, addresses: Vec<String>, network: Option<&str>) -> Contact {
+        Contact {
+            name: name.to_string(),
+            addresses,
+            network: network.map(|network| network.to_string()),
+        }
+    }
+
+    fn address_book(contacts: Vec<Contact>) -> AddressBook {
+        AddressBook::new(
+            contacts
+                .into_iter()
+                .map(|contact| (contact.name.clone(), contact))
+                .collect(),
+        )
+    }
+
+    fn output(address: String) -> TransferOutput {
+        TransferOutput {
+            address,
+            amount: 1_000_000,
+            output_kind: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn contact_resolution() {
+        let address_book = address_book(vec![
+            contact(
+                "alice",
+                vec![address(1).to_bech32("iota"), address(2).to_bech32("atoi")],
+                None,
+            ),
+            contact("bob", vec![address(3).to_bech32("iota")], Some("iota")),
+            contact("carol", vec![address(4).to_bech32("iota")], None),
+        ]);
+
+        // contacts are resolved to their address for the network, addresses are kept
+        let mut outputs = vec![output("alice".to_string()), output(address(5).to_bech32("atoi"))];
+        resolve_contacts(&address_book, &mut outputs, "atoi").await.unwrap();
+        assert_eq!(outputs[0].address, address(2).to_bech32("atoi"));
+        assert_eq!(outputs[1].address, address(5).to_bech32("atoi"));
+
+        for name in ["bob", "carol", "dave"] {
+            assert!(matches!(
+                resolve_contacts(&address_book, &mut [output(name.to_string())], "atoi").await,
+                Err(crate::Error::ContactError(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn contact_names() {
+        let address_book = address_book(vec![contact("alice", vec![address(1).to_bech32("atoi")], None)]);
+        let mut transactions = vec![transaction(
+            &[output_id(1, 0)],
+            &[(address(1), 1_000_000), (address(2), 2_000_000)],
+        )];
+        set_contact_names(&address_book, &mut transactions, "atoi").await;
+        assert_eq!(transactions[0].contacts.len(), 1);
+        assert_eq!(transactions[0].contacts[&address(1).to_bech32("atoi")], "alice");
+    }
+}
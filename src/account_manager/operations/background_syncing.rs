@@ -9,8 +9,13 @@ use std::{sync::atomic::Ordering, time::Duration};
 
 /// The default interval for background syncing
 pub(crate) const DEFAUTL_BACKGROUNDSYNCING_INTERVAL: Duration = Duration::from_secs(7);
+/// The default interval for background syncing while the MQTT subscriptions are active, syncing is then only a
+/// fallback for missed events
+#[cfg(feature = "mqtt")]
+pub(crate) const DEFAULT_MQTT_BACKGROUNDSYNCING_INTERVAL: Duration = Duration::from_secs(60);
 
-/// Start the background syncing process for all accounts, default interval is 7 seconds
+/// Start the background syncing process for all accounts, default interval is 7 seconds. With the `mqtt` feature the
+/// accounts are also updated from MQTT subscriptions, if the broker is reachable the default interval is 60 seconds.
 pub async fn start_background_syncing(
     account_manager: &AccountManager,
     options: Option<SyncOptions>,
@@ -29,6 +34,8 @@ pub async fn start_background_syncing(
 
     background_syncing_status.store(1, Ordering::Relaxed);
     let accounts = account_manager.accounts.clone();
+    #[cfg(feature = "mqtt")]
+    let client_options = account_manager.client_options.clone();
     let background_syncing = std::thread::spawn(move || {
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
@@ -36,6 +43,24 @@ pub async fn start_background_syncing(
             .unwrap();
         runtime.block_on(async {
             'outer: loop {
+                // subscribe to the topics of new addresses and pending transactions, polling continues if the broker
+                // isn't reachable
+                #[cfg(feature = "mqtt")]
+                let mqtt_subscribed = {
+                    let client_options = client_options.read().await.clone();
+                    match crate::account_manager::operations::mqtt::update_mqtt_subscriptions(
+                        accounts.clone(),
+                        &client_options,
+                    )
+                    .await
+                    {
+                        Ok(subscribed) => subscribed,
+                        Err(err) => {
+                            log::debug!("[background_syncing] mqtt error: {}", err);
+                            false
+                        }
+                    }
+                };
                 log::debug!("[background_syncing]: syncing accounts");
                 let accounts = accounts.read().await;
                 for account in accounts.iter() {
@@ -50,7 +75,15 @@ pub async fn start_background_syncing(
                     };
                 }
                 // split interval syncing to seconds so stopping the process doesn't have to wait long
-                let seconds = interval.unwrap_or(DEFAUTL_BACKGROUNDSYNCING_INTERVAL).as_secs();
+                #[cfg(feature = "mqtt")]
+                let default_interval = if mqtt_subscribed {
+                    DEFAULT_MQTT_BACKGROUNDSYNCING_INTERVAL
+                } else {
+                    DEFAUTL_BACKGROUNDSYNCING_INTERVAL
+                };
+                #[cfg(not(feature = "mqtt"))]
+                let default_interval = DEFAUTL_BACKGROUNDSYNCING_INTERVAL;
+                let seconds = interval.unwrap_or(default_interval).as_secs();
                 for second in 0..seconds {
                     if background_syncing_status.load(Ordering::Relaxed) == 2 {
                         log::debug!("[background_syncing]: stopping");
@@ -59,6 +92,9 @@ pub async fn start_background_syncing(
                     sleep(Duration::from_secs(1)).await;
                 }
             }
+            // the subscriptions run on this runtime, so they can't be used after it stopped
+            #[cfg(feature = "mqtt")]
+            crate::client::mqtt::disconnect().await;
             background_syncing_status.store(0, Ordering::Relaxed);
             log::debug!("[background_syncing]: stopped");
         });
@@ -0,0 +1,84 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    account::{
+        handle::AccountHandle,
+        operations::mqtt::{handle_metadata_event, handle_output_event},
+    },
+    client::{
+        mqtt::{update_subscriptions, TopicHandler},
+        options::ClientOptions,
+    },
+};
+
+use iota_client::{
+    bee_message::MessageId,
+    bee_rest_api::types::responses::{MessageMetadataResponse, OutputResponse},
+    TopicEvent,
+};
+use tokio::sync::RwLock;
+
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+
+/// Subscribe to the outputs of all addresses of the accounts and to the metadata of the messages of pending
+/// transactions. Returns false if MQTT is disabled in the client options.
+pub(crate) async fn update_mqtt_subscriptions(
+    accounts: Arc<RwLock<Vec<AccountHandle>>>,
+    client_options: &ClientOptions,
+) -> crate::Result<bool> {
+    if !client_options.mqtt_enabled() {
+        return Ok(false);
+    }
+    let mut topics = HashSet::new();
+    for account_handle in accounts.read().await.iter() {
+        let account = account_handle.read().await;
+        for address in account.public_addresses.iter().chain(account.internal_addresses.iter()) {
+            topics.insert(format!("addresses/{}/outputs", address.address.to_bech32()));
+        }
+        for transaction_id in &account.pending_transactions {
+            if let Some(message_id) = account.transactions.get(transaction_id).and_then(|t| t.message_id) {
+                topics.insert(format!("messages/{}/metadata", message_id));
+            }
+        }
+    }
+
+    // events are handled in tasks on the runtime of the caller, so the event loop of the subscriptions isn't blocked
+    let runtime = tokio::runtime::Handle::current();
+    let handler: TopicHandler = Arc::new(move |event: &TopicEvent| {
+        let accounts = accounts.clone();
+        let event = event.clone();
+        runtime.spawn(async move {
+            if let Err(e) = handle_topic_event(accounts, event).await {
+                log::debug!("[MQTT] error handling event: {}", e);
+            }
+        });
+    });
+    update_subscriptions(client_options, topics, handler).await?;
+    Ok(true)
+}
+
+// Update the account that the event belongs to
+async fn handle_topic_event(accounts: Arc<RwLock<Vec<AccountHandle>>>, event: TopicEvent) -> crate::Result<()> {
+    log::debug!("[MQTT] event on {}", event.topic);
+    let accounts = accounts.read().await;
+    if event.topic.ends_with("/outputs") {
+        let output_response: OutputResponse = serde_json::from_str(&event.payload)?;
+        for account_handle in accounts.iter() {
+            if handle_output_event(account_handle, output_response.clone()).await? {
+                break;
+            }
+        }
+    } else if let Some(message_id) = event
+        .topic
+        .strip_prefix("messages/")
+        .and_then(|topic| topic.strip_suffix("/metadata"))
+    {
+        let message_id = MessageId::from_str(message_id)?;
+        let metadata: MessageMetadataResponse = serde_json::from_str(&event.payload)?;
+        for account_handle in accounts.iter() {
+            handle_metadata_event(account_handle, message_id, metadata.clone()).await?;
+        }
+    }
+    Ok(())
+}
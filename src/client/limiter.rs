@@ -0,0 +1,103 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use tokio::{
+    sync::{Mutex, OwnedSemaphorePermit, Semaphore},
+    time::sleep,
+};
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Limits the requests to the nodes during syncing, with a maximum of parallel requests and optionally a maximum of
+/// requests per second. Every client has its own limiter, created from its options. The limits are shared by all nodes
+/// of the client, the node of a request is only picked by the client.
+#[derive(Debug)]
+pub(crate) struct RequestLimiter {
+    semaphore: Arc<Semaphore>,
+    token_bucket: Option<Mutex<TokenBucket>>,
+}
+
+impl RequestLimiter {
+    pub(crate) fn new(max_parallel_requests: usize, requests_per_second: Option<u32>) -> Self {
+        Self {
+            // with 0 no request could be sent
+            semaphore: Arc::new(Semaphore::new(max_parallel_requests.max(1))),
+            token_bucket: requests_per_second.map(|rate| Mutex::new(TokenBucket::new(rate, Instant::now()))),
+        }
+    }
+
+    /// Wait until a request can be sent, the request has to be done before the permit is dropped
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        if let Some(token_bucket) = &self.token_bucket {
+            loop {
+                let wait = token_bucket.lock().await.take(Instant::now());
+                match wait {
+                    Some(wait) => sleep(wait).await,
+                    None => break,
+                }
+            }
+        }
+        permit
+    }
+}
+
+// Allows `rate` requests per second, with bursts of up to `rate` requests
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32, now: Instant) -> Self {
+        let rate = f64::from(rate.max(1));
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: now,
+        }
+    }
+
+    // Take a token, returns how long to wait for the next one if there is none
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenBucket;
+
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn token_bucket_rate() {
+        let start = Instant::now();
+        let mut token_bucket = TokenBucket::new(2, start);
+        // burst up to the rate
+        assert_eq!(token_bucket.take(start), None);
+        assert_eq!(token_bucket.take(start), None);
+        assert_eq!(token_bucket.take(start), Some(Duration::from_millis(500)));
+        // one token refilled after 500ms
+        assert_eq!(token_bucket.take(start + Duration::from_millis(500)), None);
+        assert!(token_bucket.take(start + Duration::from_millis(500)).is_some());
+    }
+}
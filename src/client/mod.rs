@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod api;
-// pub(crate) mod mqtt;
+pub(crate) mod limiter;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 pub mod node;
 pub mod options;
 
@@ -11,11 +13,12 @@ use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 use url::Url;
 
-use crate::client::options::ClientOptions;
+use crate::client::{limiter::RequestLimiter, options::ClientOptions};
 
 use std::sync::Arc;
 
-type ClientInstance = Arc<RwLock<Option<Arc<Client>>>>;
+// the client with the request limiter of its options, they're always replaced together
+type ClientInstance = Arc<RwLock<Option<(Arc<Client>, Arc<RequestLimiter>)>>>;
 
 /// Gets the client instance.
 fn client_instance() -> &'static ClientInstance {
@@ -25,24 +28,52 @@ fn client_instance() -> &'static ClientInstance {
 
 pub(crate) async fn get_client() -> crate::Result<Arc<Client>> {
     let lock = client_instance().read().await;
-    if let Some(client) = &*lock {
+    if let Some((client, _)) = &*lock {
         Ok(client.clone())
     } else {
         Err(crate::Error::ClientNotSet)
     }
 }
 
+/// Gets the request limiter of the current client
+pub(crate) async fn get_request_limiter() -> crate::Result<Arc<RequestLimiter>> {
+    let lock = client_instance().read().await;
+    if let Some((_, request_limiter)) = &*lock {
+        Ok(request_limiter.clone())
+    } else {
+        Err(crate::Error::ClientNotSet)
+    }
+}
+
 pub(crate) async fn set_client(options: ClientOptions) -> crate::Result<()> {
-    let mut client_builder = ClientBuilder::new()
-        // .with_mqtt_broker_options(
-        //     options
-        //         .mqtt_broker_options()
-        //         .as_ref()
-        //         .map(|options| options.clone().into())
-        //         .unwrap_or_else(|| {
-        //             iota_client::BrokerOptions::new().automatic_disconnect(false)
-        //         }),
-        // )
+    let request_limiter = RequestLimiter::new(*options.max_parallel_requests(), *options.client_requests_per_second());
+    let client = build_client(options).await?;
+
+    let mut client_instance = client_instance().write().await;
+    client_instance.replace((Arc::new(client), Arc::new(request_limiter)));
+    drop(client_instance);
+
+    // the subscriptions were created for the old nodes
+    #[cfg(feature = "mqtt")]
+    mqtt::disconnect().await;
+
+    Ok(())
+}
+
+/// Create a client with the provided options
+pub(crate) async fn build_client(options: ClientOptions) -> crate::Result<Client> {
+    let mut client_builder = ClientBuilder::new();
+    #[cfg(feature = "mqtt")]
+    {
+        client_builder = client_builder.with_mqtt_broker_options(
+            options
+                .mqtt_broker_options()
+                .as_ref()
+                .map(|options| options.clone().into())
+                .unwrap_or_else(|| iota_client::BrokerOptions::new().automatic_disconnect(false)),
+        );
+    }
+    client_builder = client_builder
         .with_local_pow(*options.local_pow())
         .with_node_pool_urls(
             &options
@@ -124,12 +155,7 @@ pub(crate) async fn set_client(options: ClientOptions) -> crate::Result<()> {
         client_builder = client_builder.with_api_timeout(api.clone().into(), *timeout);
     }
 
-    let client = client_builder.finish().await?;
-
-    let mut client_instance = client_instance().write().await;
-    client_instance.replace(Arc::new(client));
-
-    Ok(())
+    Ok(client_builder.finish().await?)
 }
 
 fn convert_urls(urls: &[&str]) -> crate::Result<Vec<Url>> {
@@ -1,47 +1,126 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-// /// The MQTT broker options.
-// #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-// pub struct BrokerOptions {
-//     // We need to use `pub` here or these is no way to let the user create BrokerOptions
-//     #[serde(rename = "automaticDisconnect")]
-//     /// Whether the MQTT broker should be automatically disconnected when all topics are unsubscribed or not.
-//     pub automatic_disconnect: Option<bool>,
-//     /// timeout of the mqtt broker.
-//     pub timeout: Option<Duration>,
-//     /// Defines if websockets should be used (true) or TCP (false)
-//     #[serde(rename = "useWs")]
-//     pub use_ws: Option<bool>,
-//     /// Defines the port to be used for the MQTT connection
-//     pub port: Option<u16>,
-//     /// Defines the maximum reconnection attempts before it returns an error
-//     #[serde(rename = "maxReconnectionAttempts")]
-//     pub max_reconnection_attempts: Option<usize>,
-// }
-//
-// impl From<BrokerOptions> for iota_client::BrokerOptions {
-//     fn from(value: BrokerOptions) -> iota_client::BrokerOptions {
-//         let mut options = iota_client::BrokerOptions::new();
-//         if let Some(automatic_disconnect) = value.automatic_disconnect {
-//             options = options.automatic_disconnect(automatic_disconnect);
-//         }
-//         if let Some(timeout) = value.timeout {
-//             options = options.timeout(timeout);
-//         }
-//         if let Some(use_ws) = value.use_ws {
-//             options = options.use_ws(use_ws);
-//         }
-//         if let Some(port) = value.port {
-//             options = options.port(port);
-//         }
-//         if let Some(max_reconnection_attempts) = value.max_reconnection_attempts {
-//             options = options.max_reconnection_attempts(max_reconnection_attempts);
-//         }
-//         options
-//     }
-// }
-//
-// fn default_mqtt_enabled() -> bool {
-//     true
-// }
+use crate::client::options::ClientOptions;
+
+use iota_client::{Client, Topic, TopicEvent};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+/// The MQTT broker options.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BrokerOptions {
+    // We need to use `pub` here or these is no way to let the user create BrokerOptions
+    #[serde(rename = "automaticDisconnect")]
+    /// Whether the MQTT broker should be automatically disconnected when all topics are unsubscribed or not.
+    pub automatic_disconnect: Option<bool>,
+    /// timeout of the mqtt broker.
+    pub timeout: Option<Duration>,
+    /// Defines if websockets should be used (true) or TCP (false)
+    #[serde(rename = "useWs")]
+    pub use_ws: Option<bool>,
+    /// Defines the port to be used for the MQTT connection
+    pub port: Option<u16>,
+    /// Defines the maximum reconnection attempts before it returns an error
+    #[serde(rename = "maxReconnectionAttempts")]
+    pub max_reconnection_attempts: Option<usize>,
+}
+
+impl From<BrokerOptions> for iota_client::BrokerOptions {
+    fn from(value: BrokerOptions) -> iota_client::BrokerOptions {
+        let mut options = iota_client::BrokerOptions::new();
+        if let Some(automatic_disconnect) = value.automatic_disconnect {
+            options = options.automatic_disconnect(automatic_disconnect);
+        }
+        if let Some(timeout) = value.timeout {
+            options = options.timeout(timeout);
+        }
+        if let Some(use_ws) = value.use_ws {
+            options = options.use_ws(use_ws);
+        }
+        if let Some(port) = value.port {
+            options = options.port(port);
+        }
+        if let Some(max_reconnection_attempts) = value.max_reconnection_attempts {
+            options = options.max_reconnection_attempts(max_reconnection_attempts);
+        }
+        options
+    }
+}
+
+pub(crate) fn default_mqtt_enabled() -> bool {
+    true
+}
+
+/// Handler for the events of all subscribed topics
+pub(crate) type TopicHandler = Arc<dyn Fn(&TopicEvent) + Send + Sync>;
+
+// The subscriber of the iota_client needs a mutable client, so the subscriptions use an own client instance
+#[derive(Default)]
+struct MqttSubscriptions {
+    client: Option<Client>,
+    topics: HashSet<String>,
+}
+
+static MQTT_SUBSCRIPTIONS: Lazy<Mutex<MqttSubscriptions>> = Lazy::new(Default::default);
+
+/// Subscribe to the topics which aren't subscribed yet and unsubscribe from subscribed topics which aren't provided
+/// anymore. The client for the subscriptions is created with the first call.
+pub(crate) async fn update_subscriptions(
+    options: &ClientOptions,
+    topics: HashSet<String>,
+    handler: TopicHandler,
+) -> crate::Result<()> {
+    let mut subscriptions = MQTT_SUBSCRIPTIONS.lock().await;
+    if subscriptions.client.is_none() {
+        log::debug!("[MQTT] creating client");
+        subscriptions
+            .client
+            .replace(crate::client::build_client(options.clone()).await?);
+    }
+    let MqttSubscriptions {
+        client,
+        topics: subscribed_topics,
+    } = &mut *subscriptions;
+    let client = client.as_mut().expect("mqtt client was just created");
+
+    let old_topics = subscribed_topics
+        .difference(&topics)
+        .map(Topic::new)
+        .collect::<iota_client::Result<Vec<Topic>>>()?;
+    if !old_topics.is_empty() {
+        log::debug!("[MQTT] unsubscribe from {} topics", old_topics.len());
+        client.subscriber().with_topics(old_topics).unsubscribe().await?;
+    }
+    let new_topics: Vec<String> = topics.difference(subscribed_topics).cloned().collect();
+    if !new_topics.is_empty() {
+        log::debug!("[MQTT] subscribe to {} topics", new_topics.len());
+        client
+            .subscriber()
+            .with_topics(
+                new_topics
+                    .iter()
+                    .map(Topic::new)
+                    .collect::<iota_client::Result<Vec<Topic>>>()?,
+            )
+            .subscribe(move |event| handler(event))
+            .await?;
+    }
+    *subscribed_topics = topics;
+    Ok(())
+}
+
+/// Disconnect from the broker and remove all subscriptions, the next subscriptions will create a new client
+pub(crate) async fn disconnect() {
+    let mut subscriptions = MQTT_SUBSCRIPTIONS.lock().await;
+    if let Some(mut client) = subscriptions.client.take() {
+        log::debug!("[MQTT] disconnecting");
+        if let Err(e) = client.subscriber().disconnect().await {
+            log::debug!("[MQTT] disconnect error: {}", e);
+        }
+    }
+    subscriptions.topics.clear();
+}
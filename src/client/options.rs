@@ -6,6 +6,8 @@ use iota_client::node_manager::validate_url;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+#[cfg(feature = "mqtt")]
+use crate::client::mqtt::{default_mqtt_enabled, BrokerOptions};
 use crate::client::{
     api::Api,
     node::{Node, NodeAuth},
@@ -37,12 +39,14 @@ pub struct ClientOptions {
     node_pool_urls: Vec<Url>,
     /// The network string.
     network: Option<String>,
-    // /// The MQTT broker options.
-    // #[serde(rename = "mqttBrokerOptions")]
-    // mqtt_broker_options: Option<BrokerOptions>,
-    // /// Enable mqtt or not.
-    // #[serde(rename = "mqttEnabled", default = "default_mqtt_enabled")]
-    // mqtt_enabled: bool,
+    /// The MQTT broker options.
+    #[cfg(feature = "mqtt")]
+    #[serde(rename = "mqttBrokerOptions")]
+    mqtt_broker_options: Option<BrokerOptions>,
+    /// Enable mqtt or not.
+    #[cfg(feature = "mqtt")]
+    #[serde(rename = "mqttEnabled", default = "default_mqtt_enabled")]
+    mqtt_enabled: bool,
     /// Enable local proof-of-work or not.
     #[serde(rename = "localPow", default = "default_local_pow")]
     local_pow: bool,
@@ -58,11 +62,21 @@ pub struct ClientOptions {
     /// The API timeout.
     #[serde(rename = "apiTimeout", default)]
     api_timeout: HashMap<Api, Duration>,
+    /// Maximum amount of requests that are sent in parallel during syncing.
+    #[serde(rename = "maxParallelRequests", default = "default_max_parallel_requests")]
+    max_parallel_requests: usize,
+    /// Maximum amount of requests per second during syncing, shared by all nodes of the client. Not limited if not
+    /// set.
+    #[serde(rename = "clientRequestsPerSecond", default)]
+    client_requests_per_second: Option<u32>,
 }
 
 pub fn default_local_pow() -> bool {
     true
 }
+pub fn default_max_parallel_requests() -> usize {
+    100
+}
 pub fn default_node_sync_enabled() -> bool {
     true
 }
@@ -72,6 +86,17 @@ impl ClientOptions {
     pub fn builder() -> ClientOptionsBuilder {
         ClientOptionsBuilder::new()
     }
+
+    // the mqtt options are only compared with the mqtt feature
+    #[cfg(feature = "mqtt")]
+    fn mqtt_eq(&self, other: &Self) -> bool {
+        self.mqtt_broker_options == other.mqtt_broker_options && self.mqtt_enabled == other.mqtt_enabled
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    fn mqtt_eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
 impl Hash for ClientOptions {
@@ -81,9 +106,12 @@ impl Hash for ClientOptions {
         self.nodes.hash(state);
         self.node_pool_urls.hash(state);
         self.network.hash(state);
-        // self.mqtt_broker_options.hash(state);
+        #[cfg(feature = "mqtt")]
+        self.mqtt_broker_options.hash(state);
         self.local_pow.hash(state);
         self.request_timeout.hash(state);
+        self.max_parallel_requests.hash(state);
+        self.client_requests_per_second.hash(state);
     }
 }
 
@@ -94,9 +122,11 @@ impl PartialEq for ClientOptions {
             && self.nodes == other.nodes
             && self.node_pool_urls == other.node_pool_urls
             && self.network == other.network
-            // && self.mqtt_broker_options == other.mqtt_broker_options
             && self.local_pow == other.local_pow
             && self.request_timeout == other.request_timeout
+            && self.max_parallel_requests == other.max_parallel_requests
+            && self.client_requests_per_second == other.client_requests_per_second
+            && self.mqtt_eq(other)
     }
 }
 
@@ -107,13 +137,17 @@ pub struct ClientOptionsBuilder {
     nodes: Vec<Node>,
     node_pool_urls: Vec<Url>,
     network: Option<String>,
-    // mqtt_broker_options: Option<BrokerOptions>,
-    // mqtt_enabled: bool,
+    #[cfg(feature = "mqtt")]
+    mqtt_broker_options: Option<BrokerOptions>,
+    #[cfg(feature = "mqtt")]
+    mqtt_enabled: bool,
     local_pow: bool,
     node_sync_interval: Option<Duration>,
     node_sync_enabled: bool,
     request_timeout: Option<Duration>,
     api_timeout: HashMap<Api, Duration>,
+    max_parallel_requests: usize,
+    client_requests_per_second: Option<u32>,
 }
 
 impl Default for ClientOptionsBuilder {
@@ -124,13 +158,17 @@ impl Default for ClientOptionsBuilder {
             nodes: Vec::new(),
             node_pool_urls: Vec::new(),
             network: None,
-            // mqtt_broker_options: None,
-            // mqtt_enabled: default_mqtt_enabled(),
+            #[cfg(feature = "mqtt")]
+            mqtt_broker_options: None,
+            #[cfg(feature = "mqtt")]
+            mqtt_enabled: default_mqtt_enabled(),
             local_pow: default_local_pow(),
             node_sync_interval: None,
             node_sync_enabled: default_node_sync_enabled(),
             request_timeout: None,
             api_timeout: Default::default(),
+            max_parallel_requests: default_max_parallel_requests(),
+            client_requests_per_second: None,
         }
     }
 }
@@ -269,17 +307,19 @@ impl ClientOptionsBuilder {
         self
     }
 
-    // /// Sets the MQTT broker options.
-    // pub fn with_mqtt_mqtt_broker_options(mut self, options: BrokerOptions) -> Self {
-    //     self.mqtt_broker_options.replace(options);
-    //     self
-    // }
+    /// Sets the MQTT broker options.
+    #[cfg(feature = "mqtt")]
+    pub fn with_mqtt_broker_options(mut self, options: BrokerOptions) -> Self {
+        self.mqtt_broker_options.replace(options);
+        self
+    }
 
-    // /// Sets the MQTT broker options.
-    // pub fn with_mqtt_disabled(mut self) -> Self {
-    //     self.mqtt_enabled = false;
-    //     self
-    // }
+    /// Disables the MQTT subscriptions, background syncing only polls the nodes then.
+    #[cfg(feature = "mqtt")]
+    pub fn with_mqtt_disabled(mut self) -> Self {
+        self.mqtt_enabled = false;
+        self
+    }
 
     /// Sets whether the PoW should be done locally or remotely.
     pub fn with_local_pow(mut self, local: bool) -> Self {
@@ -299,6 +339,20 @@ impl ClientOptionsBuilder {
         self
     }
 
+    /// Sets the maximum amount of requests that are sent in parallel during syncing.
+    pub fn with_max_parallel_requests(mut self, max_parallel_requests: usize) -> Self {
+        self.max_parallel_requests = max_parallel_requests;
+        self
+    }
+
+    /// Limits the requests per second during syncing, to stay below the rate limit of public nodes. The limit is
+    /// shared by all nodes of the client, because the client picks the node of each request, so with multiple nodes
+    /// it should be the rate limit of the strictest node.
+    pub fn with_client_requests_per_second(mut self, client_requests_per_second: u32) -> Self {
+        self.client_requests_per_second.replace(client_requests_per_second);
+        self
+    }
+
     /// Builds the options.
     pub fn finish(self) -> crate::Result<ClientOptions> {
         let options = ClientOptions {
@@ -307,13 +361,17 @@ impl ClientOptionsBuilder {
             nodes: self.nodes,
             node_pool_urls: self.node_pool_urls,
             network: self.network,
-            // mqtt_broker_options: self.mqtt_broker_options,
-            // mqtt_enabled: self.mqtt_enabled,
+            #[cfg(feature = "mqtt")]
+            mqtt_broker_options: self.mqtt_broker_options,
+            #[cfg(feature = "mqtt")]
+            mqtt_enabled: self.mqtt_enabled,
             local_pow: self.local_pow,
             node_sync_interval: self.node_sync_interval,
             node_sync_enabled: self.node_sync_enabled,
             request_timeout: self.request_timeout,
             api_timeout: self.api_timeout,
+            max_parallel_requests: self.max_parallel_requests,
+            client_requests_per_second: self.client_requests_per_second,
         };
         Ok(options)
     }
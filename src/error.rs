@@ -248,6 +248,39 @@ pub enum Error {
     /// Empty output amount error
     #[error("output amount can't be 0")]
     EmptyOutputAmount,
+    /// Transaction not found in the account
+    #[error("transaction {0} not found")]
+    TransactionNotFound(iota_client::bee_message::payload::transaction::TransactionId),
+    /// Transaction isn't pending anymore, so it can't be cancelled or replaced
+    #[error("transaction {0} isn't pending")]
+    TransactionNotPending(iota_client::bee_message::payload::transaction::TransactionId),
+    /// Invalid replacement for a pending transaction
+    #[error("invalid transaction replacement: {0}")]
+    InvalidTransactionReplacement(String),
+    /// Output not found in the unspent outputs of the account
+    #[error("output {0} not found")]
+    OutputNotFound(iota_client::bee_message::output::OutputId),
+    /// Address with a bech32 hrp of another network
+    #[error("address {0} doesn't belong to the network with the bech32 hrp {1}")]
+    InvalidAddressNetwork(String, String),
+    /// Address book error, for example an unknown contact
+    #[error("contact error: {0}")]
+    ContactError(String),
+    /// Transfer violates the spending policy of the account
+    #[error("spending policy violation: {0}")]
+    SpendingPolicyViolation(String),
+    /// Transaction rejected by the approval hook
+    #[error("transaction rejected: {0}")]
+    TransactionRejected(String),
+    /// Idempotency key used for a different transfer or for a transfer which is still in progress
+    #[error("idempotency key conflict: {0}")]
+    IdempotencyKeyConflict(String),
+    /// Transaction didn't get confirmed within the timeout
+    #[error("transaction {0} wasn't confirmed within the timeout")]
+    ConfirmationTimeout(iota_client::bee_message::payload::transaction::TransactionId),
+    /// Syncing was cancelled with its cancellation token
+    #[error("syncing cancelled")]
+    SyncCancelled,
 }
 
 // impl Drop for Error {
@@ -400,6 +433,19 @@ impl serde::Serialize for Error {
             Self::ClientNotSet => serialize_variant(self, serializer, "ClientNotSet"),
             Self::BeeCommonLogger(_) => serialize_variant(self, serializer, "BeeCommonLogger"),
             Self::EmptyOutputAmount => serialize_variant(self, serializer, "EmptyOutputAmount"),
+            Self::TransactionNotFound(_) => serialize_variant(self, serializer, "TransactionNotFound"),
+            Self::TransactionNotPending(_) => serialize_variant(self, serializer, "TransactionNotPending"),
+            Self::InvalidTransactionReplacement(_) => {
+                serialize_variant(self, serializer, "InvalidTransactionReplacement")
+            }
+            Self::OutputNotFound(_) => serialize_variant(self, serializer, "OutputNotFound"),
+            Self::InvalidAddressNetwork(_, _) => serialize_variant(self, serializer, "InvalidAddressNetwork"),
+            Self::ContactError(_) => serialize_variant(self, serializer, "ContactError"),
+            Self::SpendingPolicyViolation(_) => serialize_variant(self, serializer, "SpendingPolicyViolation"),
+            Self::TransactionRejected(_) => serialize_variant(self, serializer, "TransactionRejected"),
+            Self::IdempotencyKeyConflict(_) => serialize_variant(self, serializer, "IdempotencyKeyConflict"),
+            Self::ConfirmationTimeout(_) => serialize_variant(self, serializer, "ConfirmationTimeout"),
+            Self::SyncCancelled => serialize_variant(self, serializer, "SyncCancelled"),
         }
     }
 }
@@ -38,6 +38,9 @@ impl EventEmitter {
                 WalletEventType::TransactionInclusion,
                 WalletEventType::TransferProgress,
                 WalletEventType::ConsolidationRequired,
+                WalletEventType::SpendingPolicyViolation,
+                WalletEventType::IncomingTransaction,
+                WalletEventType::SyncProgress,
                 #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
                 WalletEventType::LedgerAddressGeneration,
             ] {
@@ -59,6 +62,9 @@ impl EventEmitter {
             WalletEvent::TransactionInclusion(_) => WalletEventType::TransactionInclusion,
             WalletEvent::TransferProgress(_) => WalletEventType::TransferProgress,
             WalletEvent::ConsolidationRequired => WalletEventType::ConsolidationRequired,
+            WalletEvent::SpendingPolicyViolation(_) => WalletEventType::SpendingPolicyViolation,
+            WalletEvent::IncomingTransaction(_) => WalletEventType::IncomingTransaction,
+            WalletEvent::SyncProgress(_) => WalletEventType::SyncProgress,
             #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
             WalletEvent::LedgerAddressGeneration(_) => WalletEventType::LedgerAddressGeneration,
         };
@@ -143,6 +149,7 @@ mod tests {
                 )
                 .expect("Invalid tx id"),
                 inclusion_state: InclusionState::Confirmed,
+                conflict_reason: None,
             }),
         );
 
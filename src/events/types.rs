@@ -1,7 +1,10 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::account::types::{address::AddressWrapper, InclusionState};
+use crate::account::{
+    operations::syncing::SyncPhase,
+    types::{address::AddressWrapper, ConflictReason, InclusionState},
+};
 
 use getset::Getters;
 use iota_client::bee_message::payload::transaction::TransactionId;
@@ -25,6 +28,9 @@ pub enum WalletEvent {
     TransactionInclusion(TransactionInclusionEvent),
     TransferProgress(TransferProgressEvent),
     ConsolidationRequired,
+    SpendingPolicyViolation(SpendingPolicyViolationEvent),
+    IncomingTransaction(IncomingTransactionEvent),
+    SyncProgress(SyncProgressEvent),
     #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
     LedgerAddressGeneration(AddressData),
 }
@@ -35,6 +41,9 @@ pub enum WalletEventType {
     TransactionInclusion,
     TransferProgress,
     ConsolidationRequired,
+    SpendingPolicyViolation,
+    IncomingTransaction,
+    SyncProgress,
     #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
     LedgerAddressGeneration,
 }
@@ -54,6 +63,34 @@ pub struct BalanceChangeEvent {
 pub struct TransactionInclusionEvent {
     pub transaction_id: TransactionId,
     pub inclusion_state: InclusionState,
+    /// Why the transaction is conflicting, if the node provided a reason
+    pub conflict_reason: Option<ConflictReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct SpendingPolicyViolationEvent {
+    /// Why the transfer violates the spending policy
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct IncomingTransactionEvent {
+    pub transaction_id: TransactionId,
+    /// Amount sent to addresses of the account
+    pub amount: u64,
+    /// Bech32 addresses which signed the inputs
+    pub senders: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct SyncProgressEvent {
+    pub phase: SyncPhase,
+    /// Synced items of the phase
+    pub done: usize,
+    /// All items of the phase
+    pub total: usize,
+    /// Time since the syncing started in milliseconds
+    pub elapsed: u128,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -172,7 +172,7 @@ mod tests {
         let mnemonic = "giant dynamic museum toddler six deny defense ostrich bomb access mercy blood explain muscle shoot shallow glad autumn author calm heavy hawk abuse rally".to_string();
         let _ = super::MnemonicSigner.store_mnemonic(&Path::new(""), mnemonic).await;
         #[cfg(not(feature = "events"))]
-        let account_handle = AccountBuilder::new(Default::default(), SignerType::Mnemonic)
+        let account_handle = AccountBuilder::new(Default::default(), SignerType::Mnemonic, Default::default())
             .finish()
             .await
             .unwrap();
@@ -180,6 +180,7 @@ mod tests {
         let account_handle = AccountBuilder::new(
             Default::default(),
             SignerType::Mnemonic,
+            Default::default(),
             Arc::new(Mutex::new(EventEmitter::new())),
         )
         .finish()
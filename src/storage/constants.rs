@@ -16,6 +16,8 @@ pub(crate) const ACCOUNT_MANAGER_INDEXATION_KEY: &str = "iota-wallet-account-man
 
 pub(crate) const ACCOUNTS_INDEXATION_KEY: &str = "iota-wallet-accounts";
 pub(crate) const ACCOUNT_INDEXATION_KEY: &str = "iota-wallet-account-";
+pub(crate) const OUTPUT_LOCKS_INDEXATION_KEY: &str = "iota-wallet-output-locks-";
+pub(crate) const ADDRESS_BOOK_INDEXATION_KEY: &str = "iota-wallet-address-book";
 
 #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
 // Key to store the first address in the db so it can be used to verify that new accounts use the same mnemonic
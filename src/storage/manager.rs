@@ -2,17 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    account::Account,
-    account_manager::builder::AccountManagerBuilder,
+    account::{types::OutputLock, Account},
+    account_manager::{builder::AccountManagerBuilder, operations::address_book::Contact},
     storage::{constants::*, decrypt_record, Storage, StorageAdapter},
 };
 
+use iota_client::bee_message::output::OutputId;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     sync::Arc,
@@ -165,12 +166,53 @@ impl StorageManager {
         self.storage
             .remove(&format!("{}{}", ACCOUNT_INDEXATION_KEY, account_index))
             .await?;
+        self.storage
+            .remove(&format!("{}{}", OUTPUT_LOCKS_INDEXATION_KEY, account_index))
+            .await?;
         self.account_indexes.remove(&account_index);
         self.storage
             .set(ACCOUNTS_INDEXATION_KEY, self.account_indexes.clone())
             .await
     }
 
+    pub async fn save_output_locks(
+        &mut self,
+        account_index: usize,
+        output_locks: &HashMap<OutputId, OutputLock>,
+    ) -> crate::Result<()> {
+        self.storage
+            .set(
+                &format!("{}{}", OUTPUT_LOCKS_INDEXATION_KEY, account_index),
+                output_locks,
+            )
+            .await
+    }
+
+    pub async fn get_output_locks(&self, account_index: usize) -> crate::Result<HashMap<OutputId, OutputLock>> {
+        match self
+            .storage
+            .get(&format!("{}{}", OUTPUT_LOCKS_INDEXATION_KEY, account_index))
+            .await
+        {
+            Ok(record) => Ok(serde_json::from_str(&record)?),
+            // accounts without locked outputs or stored by an older version don't have a journal
+            Err(crate::Error::RecordNotFound) => Ok(HashMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn save_address_book(&mut self, address_book: &HashMap<String, Contact>) -> crate::Result<()> {
+        self.storage.set(ADDRESS_BOOK_INDEXATION_KEY, address_book).await
+    }
+
+    pub async fn get_address_book(&self) -> crate::Result<HashMap<String, Contact>> {
+        match self.storage.get(ADDRESS_BOOK_INDEXATION_KEY).await {
+            Ok(record) => Ok(serde_json::from_str(&record)?),
+            Err(crate::Error::RecordNotFound) => Ok(HashMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
     #[cfg(any(feature = "ledger-nano", feature = "ledger-nano-simulator"))]
     // used for ledger accounts to verify that the same menmonic is used for all accounts
     pub async fn save_first_ledger_address(